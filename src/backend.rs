@@ -0,0 +1,218 @@
+//! The programs that can run the game: the MIPS assignment under mipsy or
+//! SPIM, an executable that speaks the same protocol, like a C reference
+//! solution, or the wrapper's own reference version of the game.
+
+use crate::{mipsy, reference, scores};
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    path::Path,
+    process::{Child, Command, ExitStatus, Stdio},
+    thread::{self, JoinHandle},
+};
+
+pub const DEFAULT_SPIM_PATH: &str = "spim";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// Run the assembly file with mipsy.
+    #[default]
+    Mipsy,
+    /// Run the assembly file with SPIM.
+    Spim,
+    /// Run the file itself as a program.
+    Native,
+    /// Run the wrapper's built-in version of the game. The file name is ignored.
+    Reference,
+}
+
+#[derive(Clone, Debug)]
+pub enum Backend {
+    Mipsy { path: String, source: mipsy::Source },
+    Spim { path: String },
+    Native,
+    Reference,
+}
+
+pub type GameStdin = Box<dyn Write + Send>;
+pub type GameStdout = Box<dyn Read + Send>;
+pub type GameStderr = Box<dyn Read + Send>;
+
+/// A running game. Like a [`Child`], its pipes can be taken to hand to other threads.
+pub struct GameProcess {
+    pub stdin: Option<GameStdin>,
+    pub stdout: Option<GameStdout>,
+    /// Only piped if asked for. The reference game never has one.
+    pub stderr: Option<GameStderr>,
+    runner: Runner,
+}
+
+enum Runner {
+    Process(Child),
+    Thread(JoinHandle<io::Result<()>>),
+}
+
+impl GameProcess {
+    /// The game's process ID, unless it's running in-process.
+    pub fn id(&self) -> Option<u32> {
+        match &self.runner {
+            Runner::Process(child) => Some(child.id()),
+            Runner::Thread(_) => None,
+        }
+    }
+
+    /// Stops the game's process. The reference game can't be killed, but ends
+    /// once its stdin is closed.
+    pub fn kill(&mut self) {
+        if let Runner::Process(child) = &mut self.runner {
+            // It may have exited already, which is just as good
+            let _ = child.kill();
+        }
+    }
+
+    /// Waits for the game to finish. The reference game finishing counts as success.
+    pub fn wait(self) -> Result<ExitStatus> {
+        match self.runner {
+            Runner::Process(mut child) => Ok(child.wait()?),
+            Runner::Thread(handle) => match handle.join() {
+                Ok(Ok(())) => Ok(ExitStatus::default()),
+                // The wrapper stopped listening, as it does to a process when the game is over
+                Ok(Err(error)) if error.kind() == io::ErrorKind::BrokenPipe => {
+                    Ok(ExitStatus::default())
+                }
+                Ok(Err(error)) => Err(error).context("The reference game failed"),
+                Err(_) => Err(anyhow!("The reference game panicked")),
+            },
+        }
+    }
+}
+
+impl Backend {
+    /// Sets up the backend, looking for a working mipsy if it's needed.
+    /// `mipsy_path` is from the command line, `config_mipsy_path` from the config file.
+    pub fn new(
+        kind: BackendKind,
+        mipsy_path: Option<String>,
+        config_mipsy_path: Option<String>,
+        spim_path: Option<String>,
+    ) -> Result<Self> {
+        Ok(match kind {
+            BackendKind::Mipsy => {
+                let (path, source) = mipsy::find(mipsy_path, config_mipsy_path)?;
+                Backend::Mipsy { path, source }
+            }
+            BackendKind::Spim => Backend::Spim {
+                path: spim_path.unwrap_or(DEFAULT_SPIM_PATH.to_string()),
+            },
+            BackendKind::Native => Backend::Native,
+            BackendKind::Reference => Backend::Reference,
+        })
+    }
+
+    /// Starts the game with its stdin and stdout piped. Its stderr is also
+    /// piped if `capture_stderr` is set, otherwise it goes to the terminal.
+    pub fn spawn(&self, file_name: &str, capture_stderr: bool) -> Result<GameProcess> {
+        let (mut command, error) = match self {
+            Backend::Mipsy { path, .. } => {
+                let mut command = Command::new(path);
+                command.arg(file_name);
+                (command, "Failed to spawn mipsy. Try providing the path to mipsy via --mipsy-path /path/to/mipsy.".to_string())
+            }
+            Backend::Spim { path } => {
+                let mut command = Command::new(path);
+                command.arg("-file").arg(file_name);
+                (command, "Failed to spawn SPIM. Try providing the path to spim via --spim-path /path/to/spim.".to_string())
+            }
+            // A bare file name would be looked up on the PATH rather than in the current directory
+            Backend::Native => (
+                Command::new(Path::new(".").join(file_name)),
+                format!("Failed to run {}", file_name),
+            ),
+            Backend::Reference => return spawn_reference(),
+        };
+        let stderr = match capture_stderr {
+            true => Stdio::piped(),
+            false => Stdio::inherit(),
+        };
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(stderr)
+            .spawn()
+            .context(error)?;
+        Ok(GameProcess {
+            stdin: child.stdin.take().map(|stdin| Box::new(stdin) as GameStdin),
+            stdout: child.stdout.take().map(|stdout| Box::new(stdout) as GameStdout),
+            stderr: child.stderr.take().map(|stderr| Box::new(stderr) as GameStderr),
+            runner: Runner::Process(child),
+        })
+    }
+
+    /// A hash identifying the program being run, for telling runs of
+    /// different versions apart.
+    pub fn program_hash(&self, file_name: &str) -> Result<String> {
+        match self {
+            Backend::Reference => Ok(format!("{:016x}", scores::fnv1a(b"reference"))),
+            _ => scores::hash_file(Path::new(file_name)),
+        }
+    }
+
+    /// The interpreter's version, as it reports it. Native programs don't have one.
+    pub fn version(&self) -> Result<Option<String>> {
+        let (path, flag) = match self {
+            Backend::Mipsy { path, .. } => (path, "--version"),
+            Backend::Spim { path } => (path, "-version"),
+            Backend::Native | Backend::Reference => return Ok(None),
+        };
+        let output = Command::new(path)
+            .arg(flag)
+            .stdin(Stdio::null())
+            .output()
+            .with_context(|| format!("Failed to run {} {}", path, flag))?;
+        // SPIM prints its version to stderr
+        let printed = [output.stdout, output.stderr].concat();
+        let version = String::from_utf8_lossy(&printed)
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(str::to_string);
+        Ok(version)
+    }
+
+    /// The backend and its version, for telling the user what's running the game.
+    pub fn describe(&self) -> String {
+        match self.version() {
+            Ok(Some(version)) => format!("{} ({})", self, version),
+            Ok(None) => self.to_string(),
+            Err(_) => format!("{} (unknown version)", self),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backend::Mipsy { path, source } => write!(f, "mipsy at {} from {}", path, source),
+            Backend::Spim { path } => write!(f, "SPIM at {}", path),
+            Backend::Native => write!(f, "a native executable"),
+            Backend::Reference => write!(f, "the built-in reference game"),
+        }
+    }
+}
+
+/// Runs the reference game on a thread, connected with pipes just like a process.
+fn spawn_reference() -> Result<GameProcess> {
+    let (game_stdin, stdin) = io::pipe()?;
+    let (stdout, game_stdout) = io::pipe()?;
+    let handle = thread::spawn(move || reference::run(game_stdin, game_stdout));
+    Ok(GameProcess {
+        stdin: Some(Box::new(stdin)),
+        stdout: Some(Box::new(stdout)),
+        stderr: None,
+        runner: Runner::Thread(handle),
+    })
+}
@@ -0,0 +1,236 @@
+//! Bots that play the game by themselves, choosing a move after each tick.
+//!
+//! Besides the built-in strategies, a bot can be any program that speaks JSON
+//! lines: it gets the frame drawn after each tick as one line of JSON on
+//! stdin, and answers each with one line on stdout, either a move (`jump`,
+//! `left`, `crouch`, `right` or `quit`, or its key `w`, `a`, `s`, `d` or `q`)
+//! or an empty line to do nothing.
+
+use crate::frame::{Cell, Frame, Object, Position};
+use crate::keymap::Action;
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Serialize;
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// How many rows ahead the greedy bot starts dodging an obstacle.
+const LOOKAHEAD: usize = 3;
+const MOVES: [Action; 4] = [Action::Jump, Action::Left, Action::Crouch, Action::Right];
+
+pub trait Bot: Send {
+    /// Picks the move to make after seeing `frame`, if any. `tick` is how many
+    /// ticks the game had been sent and `elapsed` is the game time.
+    fn choose(&mut self, frame: &Frame, tick: u64, elapsed: Duration) -> Result<Option<Action>>;
+
+    /// The bot's program, if it runs as one.
+    fn process(&self) -> Option<BotProcess> {
+        None
+    }
+}
+
+/// A bot's program, which can be stopped from another thread while the bot
+/// is waiting for it to answer.
+#[derive(Clone)]
+pub struct BotProcess(Arc<Mutex<Child>>);
+
+impl BotProcess {
+    pub fn kill(&self) {
+        let mut child = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        // Does nothing once the program has been reaped, so there's no risk of hitting a reused pid
+        let _ = child.kill();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Strategy {
+    /// Dodges into the lane with the most room when an obstacle gets close,
+    /// and jumps a train or crouches under a barrier when there's nowhere to go.
+    Greedy,
+    /// Makes a random move on about half of the ticks.
+    Random,
+}
+
+/// Which bot plays the game.
+#[derive(Clone, Debug)]
+pub enum BotConfig {
+    BuiltIn(Strategy),
+    External { program: PathBuf, args: Vec<String> },
+}
+
+impl BotConfig {
+    /// Builds the bot, starting its program if it's an external one. The seed
+    /// makes the random bot repeatable.
+    pub fn build(&self, seed: i32) -> Result<Box<dyn Bot>> {
+        Ok(match self {
+            BotConfig::BuiltIn(Strategy::Greedy) => Box::new(GreedyBot),
+            BotConfig::BuiltIn(Strategy::Random) => Box::new(RandomBot {
+                rng: StdRng::seed_from_u64(seed as u64),
+            }),
+            BotConfig::External { program, args } => Box::new(ExternalBot::spawn(program, args)?),
+        })
+    }
+}
+
+struct GreedyBot;
+
+impl Bot for GreedyBot {
+    fn choose(&mut self, frame: &Frame, _tick: u64, _elapsed: Duration) -> Result<Option<Action>> {
+        let player = match frame.player {
+            Some(player) => player,
+            None => return Ok(None),
+        };
+        let here = clearance(frame, player, player.lane);
+        if here > LOOKAHEAD {
+            return Ok(None);
+        }
+
+        let left = player.lane.checked_sub(1);
+        let right = Some(player.lane + 1).filter(|&lane| lane < frame.lanes);
+        let best = [(Action::Left, left), (Action::Right, right)]
+            .into_iter()
+            .filter_map(|(action, lane)| Some((action, clearance(frame, player, lane?))))
+            .max_by_key(|(_, room)| *room);
+        match best {
+            Some((action, room)) if room > here => Ok(Some(action)),
+            _ => match nearest_obstacle(frame, player).map(|object| object.kind) {
+                Some(Cell::Barrier) => Ok(Some(Action::Crouch)),
+                _ => Ok(Some(Action::Jump)),
+            },
+        }
+    }
+}
+
+/// How many rows the player could run in `lane` before reaching an obstacle.
+/// An obstacle level with the player leaves no room to move into that lane.
+fn clearance(frame: &Frame, player: Position, lane: usize) -> usize {
+    frame
+        .obstacles
+        .iter()
+        .filter(|object| object.position.lane == lane && object.position.row <= player.row)
+        .map(|object| player.row - object.position.row)
+        .min()
+        .unwrap_or(usize::MAX)
+}
+
+/// The obstacle [`clearance`] measures to in the player's lane.
+fn nearest_obstacle(frame: &Frame, player: Position) -> Option<&Object> {
+    frame
+        .obstacles
+        .iter()
+        .filter(|object| object.position.lane == player.lane && object.position.row <= player.row)
+        .max_by_key(|object| object.position.row)
+}
+
+struct RandomBot {
+    rng: StdRng,
+}
+
+impl Bot for RandomBot {
+    fn choose(&mut self, _frame: &Frame, _tick: u64, _elapsed: Duration) -> Result<Option<Action>> {
+        Ok(self
+            .rng
+            .gen_bool(0.5)
+            .then(|| MOVES[self.rng.gen_range(0..MOVES.len())]))
+    }
+}
+
+struct ExternalBot {
+    child: BotProcess,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+/// What an external bot is sent for each frame.
+#[derive(Serialize)]
+struct FrameMessage<'a> {
+    tick: u64,
+    /// Game time in seconds.
+    elapsed: f64,
+    score: Option<i32>,
+    lanes: usize,
+    /// The board rows exactly as the game printed them.
+    board: Vec<String>,
+    player: Option<Position>,
+    obstacles: &'a [Object],
+    pickups: &'a [Object],
+}
+
+impl ExternalBot {
+    fn spawn(program: &Path, args: &[String]) -> Result<Self> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to start the bot {}", program.display()))?;
+        let stdin = child.stdin.take().context("No stdin for the bot")?;
+        let stdout = BufReader::new(child.stdout.take().context("No stdout for the bot")?);
+        Ok(ExternalBot {
+            child: BotProcess(Arc::new(Mutex::new(child))),
+            stdin,
+            stdout,
+        })
+    }
+}
+
+impl Bot for ExternalBot {
+    fn choose(&mut self, frame: &Frame, tick: u64, elapsed: Duration) -> Result<Option<Action>> {
+        let message = FrameMessage {
+            tick,
+            elapsed: elapsed.as_secs_f64(),
+            score: frame.score,
+            lanes: frame.lanes,
+            board: frame.board.iter().map(|row| row.iter().collect()).collect(),
+            player: frame.player,
+            obstacles: &frame.obstacles,
+            pickups: &frame.pickups,
+        };
+        let mut line = serde_json::to_string(&message)?;
+        line.push('\n');
+        self.stdin
+            .write_all(line.as_bytes())
+            .context("Couldn't send the frame to the bot")?;
+
+        let mut reply = String::new();
+        if self.stdout.read_line(&mut reply)? == 0 {
+            bail!("The bot exited without answering");
+        }
+        parse_reply(reply.trim())
+    }
+
+    fn process(&self) -> Option<BotProcess> {
+        Some(self.child.clone())
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        let mut child = self.child.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+fn parse_reply(reply: &str) -> Result<Option<Action>> {
+    if reply.is_empty() {
+        return Ok(None);
+    }
+    let mut chars = reply.chars();
+    let action = match (chars.next(), chars.next()) {
+        (Some(key), None) => Action::ALL
+            .into_iter()
+            .find(|action| action.command() == Some(key)),
+        _ => reply.parse().ok(),
+    };
+    match action {
+        Some(action) if action.command().is_some() => Ok(Some(action)),
+        _ => bail!("The bot sent '{}', which isn't a move", reply),
+    }
+}
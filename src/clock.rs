@@ -0,0 +1,72 @@
+//! The game clock, which only runs while the game isn't paused.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+struct ClockState {
+    started: Instant,
+    paused_at: Option<Instant>,
+    paused_total: Duration,
+    stopped: bool,
+}
+
+impl ClockState {
+    fn elapsed(&self) -> Duration {
+        let now = self.paused_at.unwrap_or_else(Instant::now);
+        now.duration_since(self.started) - self.paused_total
+    }
+}
+
+pub struct GameClock {
+    state: Mutex<ClockState>,
+}
+
+impl GameClock {
+    pub fn start() -> Self {
+        GameClock {
+            state: Mutex::new(ClockState {
+                started: Instant::now(),
+                paused_at: None,
+                paused_total: Duration::ZERO,
+                stopped: false,
+            }),
+        }
+    }
+
+    /// Time the game has spent running, not counting pauses.
+    pub fn elapsed(&self) -> Duration {
+        self.state.lock().unwrap().elapsed()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().paused_at.is_some()
+    }
+
+    /// Pauses a running clock or resumes a paused one. Returns whether the
+    /// clock is now paused. A stopped clock stays paused.
+    pub fn toggle_pause(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.stopped {
+            return true;
+        }
+        match state.paused_at.take() {
+            Some(paused_at) => {
+                state.paused_total += paused_at.elapsed();
+                false
+            }
+            None => {
+                state.paused_at = Some(Instant::now());
+                true
+            }
+        }
+    }
+
+    /// Stops the clock for good once the game has ended.
+    pub fn stop(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.stopped = true;
+        state.paused_at.get_or_insert_with(Instant::now);
+    }
+}
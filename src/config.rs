@@ -0,0 +1,146 @@
+//! Defaults for the command line options, read from config files.
+//!
+//! The user's config is `railroad-runners/config.toml` in their config
+//! directory (`~/.config` on Linux), and a project can have its own
+//! `.railroad-runners.toml` in the current directory. Settings in the project
+//! file override the user's, and options on the command line override both.
+//!
+//! ```toml
+//! backend = "spim"
+//! seed = "daily"
+//! keymap = "vim"
+//! bind = ["jump=space,up"]
+//! speed-curve = "log:1.5"
+//! theme = "unicode"
+//! record-dir = "replays"
+//! ```
+//!
+//! Relative paths are relative to the file they're written in.
+
+use crate::{backend::BackendKind, theme::Theme};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const LOCAL_CONFIG_FILE: &str = ".railroad-runners.toml";
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub backend: Option<BackendKind>,
+    pub mipsy_path: Option<String>,
+    pub spim_path: Option<String>,
+    pub seed: Option<SeedPolicy>,
+    pub keymap: Option<String>,
+    pub keymap_file: Option<PathBuf>,
+    pub bind: Option<Vec<String>>,
+    pub speed_curve: Option<String>,
+    pub theme: Option<String>,
+    pub name: Option<String>,
+    /// Whether games go on the leaderboard and into the daily history.
+    pub save_scores: Option<bool>,
+    pub debug: Option<bool>,
+    pub lockstep: Option<bool>,
+    /// Record every game into this directory, unless `--record` says otherwise.
+    pub record_dir: Option<PathBuf>,
+    pub max_ticks: Option<u64>,
+}
+
+/// How to pick the seed when `--seed` and `--daily` aren't given.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(untagged, expecting = "a seed, \"random\" or \"daily\"")]
+pub enum SeedPolicy {
+    Fixed(i32),
+    Named(NamedSeed),
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NamedSeed {
+    /// A new seed for every game, as without a config.
+    Random,
+    /// Always play the daily challenge.
+    Daily,
+}
+
+impl Config {
+    /// Merges the user's config and the project's, whichever of them exist.
+    pub fn load() -> Result<Self> {
+        let mut config = Config::default();
+        for path in Self::paths() {
+            if path.is_file() {
+                config = config.merge(Self::load_file(&path)?);
+            }
+        }
+        Ok(config)
+    }
+
+    /// The config files, lowest precedence first.
+    pub fn paths() -> Vec<PathBuf> {
+        let user = dirs::config_dir().map(|dir| dir.join("railroad-runners").join("config.toml"));
+        user.into_iter()
+            .chain([PathBuf::from(LOCAL_CONFIG_FILE)])
+            .collect()
+    }
+
+    fn load_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let mut config: Config = toml::from_str(&contents)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let resolve = |relative: &str| dir.join(relative).to_string_lossy().into_owned();
+        for relative in [&mut config.keymap_file, &mut config.record_dir]
+            .into_iter()
+            .flatten()
+        {
+            *relative = dir.join(&*relative);
+        }
+        // A bare program name is looked up on the PATH instead
+        for program in [&mut config.mipsy_path, &mut config.spim_path]
+            .into_iter()
+            .flatten()
+            .filter(|program| program.contains('/'))
+        {
+            *program = resolve(program);
+        }
+        if let Some(name) = config
+            .theme
+            .as_mut()
+            .filter(|name| Theme::built_in(name).is_none())
+        {
+            *name = resolve(name);
+        }
+        if let Some(curve) = &mut config.speed_curve {
+            if let Some(file) = curve.strip_prefix("table:@") {
+                *curve = format!("table:@{}", resolve(file));
+            }
+        }
+        Ok(config)
+    }
+
+    /// Settings from `over` where it has them, otherwise from `self`.
+    fn merge(self, over: Config) -> Config {
+        Config {
+            backend: over.backend.or(self.backend),
+            mipsy_path: over.mipsy_path.or(self.mipsy_path),
+            spim_path: over.spim_path.or(self.spim_path),
+            seed: over.seed.or(self.seed),
+            keymap: over.keymap.or(self.keymap),
+            keymap_file: over.keymap_file.or(self.keymap_file),
+            bind: over.bind.or(self.bind),
+            speed_curve: over.speed_curve.or(self.speed_curve),
+            theme: over.theme.or(self.theme),
+            name: over.name.or(self.name),
+            save_scores: over.save_scores.or(self.save_scores),
+            debug: over.debug.or(self.debug),
+            lockstep: over.lockstep.or(self.lockstep),
+            record_dir: over.record_dir.or(self.record_dir),
+            max_ticks: over.max_ticks.or(self.max_ticks),
+        }
+    }
+}
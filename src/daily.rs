@@ -0,0 +1,188 @@
+//! The daily challenge: everyone playing on the same UTC day gets the same
+//! seed and speed curve, and each day's results are kept in a history file.
+
+use crate::{
+    date::Date,
+    scores::{self, data_dir, ScoreEntry},
+    speed::SpeedCurve,
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::PathBuf,
+};
+
+const HISTORY_FILE: &str = "daily.toml";
+
+/// The seed for a day's challenge.
+pub fn seed(date: Date) -> i32 {
+    let hash = scores::fnv1a(format!("railroad-runners daily {}", date).as_bytes());
+    // Kept positive so it's easy to pass to `--seed` later
+    (hash % i32::MAX as u64) as i32
+}
+
+/// Every daily game runs at the default speed, so runs on the same day are comparable.
+pub fn speed_curve() -> SpeedCurve {
+    SpeedCurve::default()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct DailyRun {
+    /// The day the challenge was for, which is when the game started.
+    date: String,
+    seed: i32,
+    score: i32,
+    player: String,
+    program_hash: String,
+}
+
+/// Where a run placed on its day.
+pub struct DailyResult {
+    pub rank: usize,
+    /// Days in a row the player has played, including this one.
+    pub streak: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct DailyHistory {
+    #[serde(default)]
+    runs: Vec<DailyRun>,
+}
+
+impl DailyHistory {
+    fn path() -> Result<PathBuf> {
+        Ok(data_dir()?.join(HISTORY_FILE))
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(DailyHistory::default());
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read the daily history from {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Invalid daily history file {}", path.display()))
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        fs::write(&path, toml::to_string(self)?)
+            .with_context(|| format!("Failed to save the daily history to {}", path.display()))
+    }
+
+    /// Adds a finished daily game to the history and saves it.
+    pub fn record(date: Date, entry: &ScoreEntry) -> Result<DailyResult> {
+        let mut history = Self::load()?;
+        let rank = 1 + history
+            .runs_on(date)
+            .filter(|other| other.score > entry.score)
+            .count();
+        history.runs.push(DailyRun {
+            date: date.to_string(),
+            seed: entry.seed,
+            score: entry.score,
+            player: entry.player.clone(),
+            program_hash: entry.program_hash.clone(),
+        });
+        history.save()?;
+        Ok(DailyResult {
+            rank,
+            streak: history.streak(&entry.player, date),
+        })
+    }
+
+    fn runs_on(&self, date: Date) -> impl Iterator<Item = &DailyRun> {
+        let date = date.to_string();
+        self.runs.iter().filter(move |run| run.date == date)
+    }
+
+    /// How many days in a row up to `today` the player has played. A streak
+    /// that ended yesterday still counts, since there's time left to play today.
+    fn streak(&self, player: &str, today: Date) -> u32 {
+        let played: HashSet<&str> = self
+            .runs
+            .iter()
+            .filter(|run| run.player == player)
+            .map(|run| run.date.as_str())
+            .collect();
+        let played_on = |days: i64| played.contains(Date::from_days(days).to_string().as_str());
+
+        let mut day = today.days();
+        if !played_on(day) {
+            day -= 1;
+        }
+        let mut streak = 0;
+        while played_on(day) {
+            streak += 1;
+            day -= 1;
+        }
+        streak
+    }
+
+    /// Prints today's challenge and results, the winners of the last few days,
+    /// and everyone's current streak.
+    pub fn print(&self, today: Date, days: usize) {
+        println!("Daily challenge for {}: seed {}", today, seed(today));
+        let mut runs: Vec<&DailyRun> = self.runs_on(today).collect();
+        runs.sort_by_key(|run| std::cmp::Reverse(run.score));
+        if runs.is_empty() {
+            println!("  No runs yet today");
+        }
+        for (rank, run) in runs.iter().enumerate() {
+            println!(
+                "  {:>4}  {:>7}  {:<16}  ({})",
+                rank + 1,
+                run.score,
+                run.player,
+                &run.program_hash[..8]
+            );
+        }
+
+        let earlier = (1..days as i64)
+            .map(|ago| Date::from_days(today.days() - ago))
+            .filter_map(|date| {
+                let best = self.runs_on(date).max_by_key(|run| run.score)?;
+                Some((date, best, self.runs_on(date).count()))
+            })
+            .collect::<Vec<_>>();
+        if !earlier.is_empty() {
+            println!();
+            println!("Earlier days");
+            for (date, best, count) in earlier {
+                println!(
+                    "  {}  {:>11}  {:>7}  {:<16}  {} run{}",
+                    date,
+                    best.seed,
+                    best.score,
+                    best.player,
+                    count,
+                    if count == 1 { "" } else { "s" }
+                );
+            }
+        }
+
+        let mut streaks: BTreeMap<&str, u32> = BTreeMap::new();
+        for run in &self.runs {
+            streaks
+                .entry(&run.player)
+                .or_insert_with(|| self.streak(&run.player, today));
+        }
+        let mut streaks: Vec<_> = streaks.into_iter().filter(|(_, streak)| *streak > 0).collect();
+        if !streaks.is_empty() {
+            streaks.sort_by_key(|(_, streak)| std::cmp::Reverse(*streak));
+            println!();
+            println!("Streaks");
+            for (player, streak) in streaks {
+                println!(
+                    "  {:<16}  {} day{}",
+                    player,
+                    streak,
+                    if streak == 1 { "" } else { "s" }
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Just enough calendar maths to timestamp runs and pick daily seeds in UTC.
+
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateTime {
+    pub date: Date,
+    pub seconds_of_day: u32,
+}
+
+impl Date {
+    /// Converts days since 1970-01-01, using Howard Hinnant's `civil_from_days`.
+    pub fn from_days(days: i64) -> Date {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        Date { year, month, day }
+    }
+
+    /// Days since 1970-01-01, the inverse of `from_days`.
+    pub fn days(&self) -> i64 {
+        let year = self.year - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let month = i64::from(self.month);
+        let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5
+            + i64::from(self.day)
+            - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    pub fn today() -> Date {
+        DateTime::now().date
+    }
+}
+
+impl DateTime {
+    pub fn now() -> DateTime {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs() as i64)
+            .unwrap_or(0);
+        DateTime {
+            date: Date::from_days(seconds.div_euclid(86_400)),
+            seconds_of_day: seconds.rem_euclid(86_400) as u32,
+        }
+    }
+
+    /// The time as `YYYY-MM-DD-HHMMSS`, for file names.
+    pub fn file_stamp(&self) -> String {
+        let seconds = self.seconds_of_day;
+        format!(
+            "{}-{:02}{:02}{:02}",
+            self.date,
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.seconds_of_day;
+        write!(
+            f,
+            "{}T{:02}:{:02}:{:02}Z",
+            self.date,
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i64, month: u32, day: u32) -> Date {
+        Date { year, month, day }
+    }
+
+    #[test]
+    fn known_dates() {
+        assert_eq!(Date::from_days(0), date(1970, 1, 1));
+        assert_eq!(Date::from_days(-1), date(1969, 12, 31));
+        assert_eq!(Date::from_days(11_016), date(2000, 2, 29));
+        assert_eq!(Date::from_days(19_782), date(2024, 2, 29));
+        assert_eq!(date(2100, 3, 1).days() - date(2100, 2, 28).days(), 1);
+    }
+
+    #[test]
+    fn days_round_trip() {
+        for days in (-800_000..800_000).step_by(97) {
+            assert_eq!(Date::from_days(days).days(), days);
+        }
+    }
+
+    #[test]
+    fn consecutive_days_are_consecutive_dates() {
+        let mut previous = Date::from_days(-1000);
+        for days in -999..50_000 {
+            let next = Date::from_days(days);
+            assert!(next > previous, "{} isn't after {}", next, previous);
+            assert!((1..=12).contains(&next.month) && (1..=31).contains(&next.day));
+            previous = next;
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Differential testing: runs a student's program and a reference program on
+//! the same seed and inputs, and finds the first frame where they disagree.
+
+use crate::backend::Backend;
+use crate::frame::{self, Cell, Frame};
+use crate::protocol::Input;
+use crate::script::Script;
+use crate::shutdown;
+use anyhow::{Context, Result};
+use std::thread;
+
+const MAX_CELL_DIFFS: usize = 20;
+
+#[derive(Clone, Debug)]
+pub struct DiffArgs {
+    pub student: String,
+    pub reference: String,
+    pub student_backend: Backend,
+    pub reference_backend: Backend,
+    pub seed: i32,
+    pub script: Script,
+    pub max_ticks: u64,
+}
+
+struct CellDiff {
+    row: usize,
+    col: usize,
+    student: Option<char>,
+    reference: Option<char>,
+}
+
+/// Runs both programs and prints a report. Returns whether their output matched.
+pub fn run_diff(args: &DiffArgs) -> Result<bool> {
+    let inputs = args.script.inputs(args.max_ticks);
+    let run = |backend: &Backend, file_name: &str| -> Result<String> {
+        let mut output = Vec::new();
+        crate::run_scripted(backend, file_name, args.seed, &inputs, None, &mut output)
+            .with_context(|| format!("Failed to run {}", file_name))?;
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    };
+
+    shutdown::scripted_game_started(args.seed, None);
+    let (student, reference) = thread::scope(|scope| {
+        let student = scope.spawn(|| run(&args.student_backend, &args.student));
+        let reference = scope.spawn(|| run(&args.reference_backend, &args.reference));
+        (student.join().unwrap(), reference.join().unwrap())
+    });
+    shutdown::game_finished();
+    let student = frame::parse_frames(&student?);
+    let reference = frame::parse_frames(&reference?);
+
+    let Some(index) = (0..student.len().max(reference.len()))
+        .find(|&i| student.get(i).map(|f| &f.lines) != reference.get(i).map(|f| &f.lines))
+    else {
+        println!("Outputs match for all {} frames", student.len());
+        return Ok(true);
+    };
+
+    println!(
+        "Outputs diverge at frame {}, after tick {}",
+        index,
+        tick_of_frame(&inputs, index)
+    );
+    let empty = Frame::default();
+    let student_frame = student.get(index).unwrap_or(&empty);
+    let reference_frame = reference.get(index).unwrap_or(&empty);
+    for (name, frame) in [("student", student_frame), ("reference", reference_frame)] {
+        match frame.lines.is_empty() {
+            true => println!("  {:<9}: already finished", name),
+            false => println!("  {:<9}: {}", name, summarise(frame)),
+        }
+    }
+    println!();
+    let (student_frame, reference_frame) = (&student_frame.lines, &reference_frame.lines);
+    print_side_by_side(student_frame, reference_frame);
+
+    let cells = cell_diff(student_frame, reference_frame);
+    println!();
+    println!("{} differing cells:", cells.len());
+    for cell in cells.iter().take(MAX_CELL_DIFFS) {
+        println!(
+            "  row {:>2}, col {:>2}: student {}, reference {}",
+            cell.row,
+            cell.col,
+            describe(cell.student),
+            describe(cell.reference)
+        );
+    }
+    if cells.len() > MAX_CELL_DIFFS {
+        println!("  ... and {} more", cells.len() - MAX_CELL_DIFFS);
+    }
+    Ok(false)
+}
+
+/// How many ticks the game had been sent when it drew the frame at `index`.
+/// The game draws an opening frame, then a frame after every input.
+fn tick_of_frame(inputs: &[Input], index: usize) -> usize {
+    inputs
+        .iter()
+        .take(index)
+        .filter(|&&input| input == Input::Tick)
+        .count()
+}
+
+fn summarise(frame: &Frame) -> String {
+    let mut parts = Vec::new();
+    if let Some(score) = frame.score {
+        parts.push(format!("score {}", score));
+    }
+    if let Some(player) = frame.player {
+        parts.push(format!("player in lane {}", player.lane));
+    }
+    if let (Some(player), Some(obstacle)) = (frame.player, frame.nearest_obstacle()) {
+        let kind = match obstacle.kind {
+            Cell::Train => "train",
+            _ => "barrier",
+        };
+        parts.push(format!(
+            "{} {} rows ahead",
+            kind,
+            player.row - obstacle.position.row
+        ));
+    }
+    parts.push(format!(
+        "{} obstacles and {} pickups on a {}x{} board",
+        frame.obstacles.len(),
+        frame.pickups.len(),
+        frame.board.len(),
+        frame.lanes
+    ));
+    if frame.game_over {
+        parts.push("game over".to_string());
+    }
+    parts.join(", ")
+}
+
+fn describe(cell: Option<char>) -> String {
+    match cell {
+        Some(c) => format!("{:?}", c),
+        None => "nothing".to_string(),
+    }
+}
+
+fn print_side_by_side(student: &[String], reference: &[String]) {
+    let width = student
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("student".len());
+    println!("  {:<width$} | reference", "student");
+    for row in 0..student.len().max(reference.len()) {
+        let left = student.get(row).map(String::as_str).unwrap_or("");
+        let right = reference.get(row).map(String::as_str).unwrap_or("");
+        let marker = if left == right { ' ' } else { '!' };
+        println!("{} {:<width$} | {}", marker, left, right);
+    }
+}
+
+fn cell_diff(student: &[String], reference: &[String]) -> Vec<CellDiff> {
+    let mut cells = Vec::new();
+    for row in 0..student.len().max(reference.len()) {
+        let left: Vec<char> = student.get(row).map(|line| line.chars().collect()).unwrap_or_default();
+        let right: Vec<char> = reference.get(row).map(|line| line.chars().collect()).unwrap_or_default();
+        for col in 0..left.len().max(right.len()) {
+            let (student, reference) = (left.get(col).copied(), right.get(col).copied());
+            if student != reference {
+                cells.push(CellDiff {
+                    row,
+                    col,
+                    student,
+                    reference,
+                });
+            }
+        }
+    }
+    cells
+}
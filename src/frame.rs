@@ -0,0 +1,320 @@
+//! Parsing the game's output into frames.
+//!
+//! The game redraws the whole board after every tick and finishes each redraw
+//! with its score line, so a frame is every line up to and including a
+//! `Score:` line. Anything printed after the last score line, such as the game
+//! over message, becomes a final partial frame.
+//!
+//! Board rows are the lines that start and end with a wall (`|`). If a row has
+//! walls between the lanes those split it into lanes, otherwise the space
+//! between the outer walls is split evenly into [`DEFAULT_LANES`] lanes.
+
+use serde::Serialize;
+use std::io::BufRead;
+
+pub const DEFAULT_LANES: usize = 3;
+const WALL: char = '|';
+
+/// What a character on the board represents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cell {
+    Empty,
+    Rail,
+    Wall,
+    Player,
+    Train,
+    Barrier,
+    Coin,
+    PowerUp,
+    Other,
+}
+
+impl Cell {
+    pub fn classify(c: char) -> Cell {
+        match c {
+            ' ' => Cell::Empty,
+            '.' => Cell::Rail,
+            WALL => Cell::Wall,
+            // Running, jumping and crouching
+            '@' | '^' | '_' => Cell::Player,
+            'T' | '=' | '[' | ']' => Cell::Train,
+            '#' => Cell::Barrier,
+            '$' => Cell::Coin,
+            '*' | '+' => Cell::PowerUp,
+            _ => Cell::Other,
+        }
+    }
+
+    pub fn is_obstacle(self) -> bool {
+        matches!(self, Cell::Train | Cell::Barrier)
+    }
+
+    pub fn is_pickup(self) -> bool {
+        matches!(self, Cell::Coin | Cell::PowerUp)
+    }
+}
+
+/// A place on the board. Rows count down from the top of the printed board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Position {
+    pub row: usize,
+    pub lane: usize,
+}
+
+/// Something on the board that isn't the player, e.g. a train or a coin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Object {
+    pub kind: Cell,
+    pub position: Position,
+}
+
+/// One redraw of the game, as printed and as parsed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Frame {
+    /// Every line of the frame exactly as the game printed it.
+    pub lines: Vec<String>,
+    /// The board rows as characters.
+    pub board: Vec<Vec<char>>,
+    pub lanes: usize,
+    pub player: Option<Position>,
+    pub obstacles: Vec<Object>,
+    pub pickups: Vec<Object>,
+    pub score: Option<i32>,
+    pub game_over: bool,
+}
+
+impl Frame {
+    pub fn parse(lines: Vec<String>) -> Frame {
+        let mut frame = Frame {
+            lanes: DEFAULT_LANES,
+            ..Frame::default()
+        };
+
+        for line in &lines {
+            if let Some(score) = parse_score(line) {
+                frame.score = Some(score);
+            }
+            if line.to_lowercase().contains("game over") {
+                frame.game_over = true;
+            }
+
+            if !is_board_row(line) {
+                continue;
+            }
+            let row: Vec<char> = line.trim_end().chars().collect();
+
+            let row_index = frame.board.len();
+            let lanes = lane_spans(&row);
+            frame.lanes = lanes.len();
+            for (lane, span) in lanes.into_iter().enumerate() {
+                let position = Position {
+                    row: row_index,
+                    lane,
+                };
+                // Sprites can be several characters wide, so each lane holds at most one object
+                let kinds: Vec<Cell> = row[span].iter().map(|&c| Cell::classify(c)).collect();
+                if kinds.contains(&Cell::Player) {
+                    frame.player = Some(position);
+                }
+                if let Some(&kind) = kinds.iter().find(|kind| kind.is_obstacle()) {
+                    frame.obstacles.push(Object { kind, position });
+                } else if let Some(&kind) = kinds.iter().find(|kind| kind.is_pickup()) {
+                    frame.pickups.push(Object { kind, position });
+                }
+            }
+            frame.board.push(row);
+        }
+
+        frame.lines = lines;
+        frame
+    }
+
+    /// The closest obstacle in the player's lane that is still ahead of them.
+    pub fn nearest_obstacle(&self) -> Option<&Object> {
+        let player = self.player?;
+        self.obstacles
+            .iter()
+            .filter(|object| object.position.lane == player.lane && object.position.row < player.row)
+            .max_by_key(|object| object.position.row)
+    }
+}
+
+pub fn is_board_row(line: &str) -> bool {
+    let line = line.trim_end();
+    line.len() >= 2 && line.starts_with(WALL) && line.ends_with(WALL)
+}
+
+fn parse_score(line: &str) -> Option<i32> {
+    line.trim_start()
+        .strip_prefix("Score:")?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// The character ranges of each lane in a board row, excluding walls.
+fn lane_spans(row: &[char]) -> Vec<std::ops::Range<usize>> {
+    let walls: Vec<usize> = (0..row.len()).filter(|&i| row[i] == WALL).collect();
+    if walls.len() > 2 {
+        return walls.windows(2).map(|pair| pair[0] + 1..pair[1]).collect();
+    }
+
+    let inner = row.len() - 2;
+    (0..DEFAULT_LANES)
+        .map(|lane| 1 + lane * inner / DEFAULT_LANES..1 + (lane + 1) * inner / DEFAULT_LANES)
+        .collect()
+}
+
+pub fn is_frame_end(line: &str) -> bool {
+    parse_score(line).is_some()
+}
+
+/// Collects output lines until a frame is complete.
+#[derive(Default)]
+pub struct FrameParser {
+    lines: Vec<String>,
+}
+
+impl FrameParser {
+    /// Adds a line, returning the finished frame if this line completed one.
+    pub fn push_line(&mut self, line: String) -> Option<Frame> {
+        let end = is_frame_end(&line);
+        self.lines.push(line);
+        end.then(|| Frame::parse(std::mem::take(&mut self.lines)))
+    }
+
+    /// Returns whatever was left over once the output has ended.
+    pub fn finish(self) -> Option<Frame> {
+        (!self.lines.is_empty()).then(|| Frame::parse(self.lines))
+    }
+}
+
+/// The lines of the game's output as they arrive. Unlike [`BufRead::lines`],
+/// bytes that aren't valid UTF-8 are replaced instead of ending the output there.
+pub fn output_lines(mut output: impl BufRead) -> impl Iterator<Item = String> {
+    let mut buffer = Vec::new();
+    std::iter::from_fn(move || {
+        buffer.clear();
+        match output.read_until(b'\n', &mut buffer) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => {
+                let line = buffer.strip_suffix(b"\n").unwrap_or(&buffer);
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                Some(String::from_utf8_lossy(line).into_owned())
+            }
+        }
+    })
+}
+
+pub fn parse_frames(output: &str) -> Vec<Frame> {
+    let mut parser = FrameParser::default();
+    let mut frames: Vec<_> = output
+        .lines()
+        .filter_map(|line| parser.push_line(line.to_string()))
+        .collect();
+    frames.extend(parser.finish());
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "\
+Welcome to Railroad Runners!
+| T | # |   |
+|   | @ | $ |
+Score: 0
+| T |   |   |
+|   | @ | # |
+Score: 1
+Game over, thanks for playing!
+";
+
+    #[test]
+    fn frames_end_at_score_lines() {
+        let frames = parse_frames(OUTPUT);
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].lines.len(), 4);
+        assert_eq!(frames[0].lines[0], "Welcome to Railroad Runners!");
+        assert_eq!(frames[0].score, Some(0));
+        assert_eq!(frames[1].lines.len(), 3);
+        assert_eq!(frames[1].score, Some(1));
+    }
+
+    #[test]
+    fn parser_waits_for_the_score_line() {
+        let mut parser = FrameParser::default();
+        assert_eq!(parser.push_line("|   | @ |   |".to_string()), None);
+        let frame = parser.push_line("Score: 12".to_string()).unwrap();
+        assert_eq!(frame.score, Some(12));
+        assert_eq!(parser.finish(), None);
+    }
+
+    #[test]
+    fn leftover_lines_become_a_game_over_frame() {
+        let last = parse_frames(OUTPUT).pop().unwrap();
+        assert_eq!(last.lines, ["Game over, thanks for playing!"]);
+        assert!(last.game_over);
+        assert_eq!(last.score, None);
+        assert!(last.board.is_empty());
+    }
+
+    #[test]
+    fn walls_split_lanes() {
+        let frame = &parse_frames(OUTPUT)[0];
+        assert_eq!(frame.lanes, 3);
+        assert_eq!(frame.board.len(), 2);
+        assert_eq!(frame.player, Some(Position { row: 1, lane: 1 }));
+        assert_eq!(
+            frame.obstacles,
+            [
+                Object {
+                    kind: Cell::Train,
+                    position: Position { row: 0, lane: 0 },
+                },
+                Object {
+                    kind: Cell::Barrier,
+                    position: Position { row: 0, lane: 1 },
+                },
+            ]
+        );
+        assert_eq!(
+            frame.pickups,
+            [Object {
+                kind: Cell::Coin,
+                position: Position { row: 1, lane: 2 },
+            }]
+        );
+        assert!(!frame.game_over);
+    }
+
+    #[test]
+    fn rows_without_inner_walls_split_evenly() {
+        let frame = Frame::parse(vec!["|#..$..T..|".to_string(), "|...@.....|".to_string()]);
+        assert_eq!(frame.lanes, DEFAULT_LANES);
+        assert_eq!(frame.player, Some(Position { row: 1, lane: 1 }));
+        let kinds: Vec<_> = frame.obstacles.iter().map(|o| (o.kind, o.position.lane)).collect();
+        assert_eq!(kinds, [(Cell::Barrier, 0), (Cell::Train, 2)]);
+        assert_eq!(frame.pickups[0].position.lane, 1);
+    }
+
+    #[test]
+    fn nearest_obstacle_is_ahead_in_the_players_lane() {
+        let frame = Frame::parse(
+            ["| # |   |", "|   | T |", "| T |   |", "| @ |   |"]
+                .map(String::from)
+                .to_vec(),
+        );
+        assert_eq!(frame.nearest_obstacle().unwrap().position.row, 2);
+    }
+
+    #[test]
+    fn output_lines_survive_invalid_utf8() {
+        let output: &[u8] = b"| \xff |\r\nScore: 3\nlast";
+        let lines: Vec<_> = output_lines(output).collect();
+        assert_eq!(lines, ["| \u{fffd} |", "Score: 3", "last"]);
+    }
+}
@@ -0,0 +1,478 @@
+//! Playing a game in the terminal.
+//!
+//! Everything that can happen during a game arrives as an [`Event`] on one
+//! channel: lines from the game's stdout and stderr, keys, the bot's moves and
+//! signals, each read on its own small thread. A single loop handles them in
+//! order and owns everything they affect, so nothing else needs locking.
+//! Ticks come from the loop itself, whenever it has waited long enough for
+//! the next one without hearing anything else.
+
+use crate::{
+    backend::{GameStderr, GameStdout},
+    bot::Bot,
+    clock::GameClock,
+    frame::{self, Frame, FrameParser},
+    hud::Hud,
+    keymap::{self, Action},
+    postmortem::{self, PostMortem, Recent},
+    protocol,
+    render::Renderer,
+    scheduler::{TickScheduler, LATE_TOLERANCE},
+    shutdown,
+    terminal::TerminalInput,
+    Args, GameInput, GameOutput, GameSummary,
+};
+use anyhow::{Error, Result};
+use console::{Key, Term};
+use std::{
+    io::{self, BufReader, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    },
+    thread,
+    time::Duration,
+};
+
+/// How often the key thread checks whether the game is over while waiting for a key.
+const KEY_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How many intervals past its deadline a tick waits for a frame in lockstep,
+/// in case the game never finishes one, e.g. because it prints its score differently.
+const FRAME_PATIENCE: u32 = 4;
+
+pub enum Event {
+    /// Time to advance the game.
+    Tick,
+    Key(Key),
+    BotMove(Action),
+    /// The bot gave up, with the reason why.
+    BotStopped(String),
+    /// A line the game printed to stdout.
+    OutputLine(String),
+    /// A line the game printed to stderr.
+    ErrorLine(String),
+    /// A whole redraw of the game, put together from its output lines.
+    FrameComplete(Frame),
+    /// The game closed its stdout, so it has exited or is about to.
+    ChildExited,
+    Signal(i32),
+}
+
+/// Whether the loop should keep going after an event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Flow {
+    Continue,
+    Stop,
+}
+
+struct GameLoop<'a> {
+    args: &'a Args,
+    clock: &'a GameClock,
+    input: GameInput<'a>,
+    renderer: Renderer,
+    parser: FrameParser,
+    output: GameOutput,
+    errors: Vec<String>,
+    /// Passes frames to the bot's thread, with the ticks sent so far, if a bot is playing.
+    bot: Option<Sender<(Frame, u64)>>,
+    /// Set by each tick until the next frame goes to the bot. The game also
+    /// redraws after every move, and passing those on would have the bot
+    /// answering its own moves.
+    bot_frame_due: bool,
+    scheduler: TickScheduler,
+    /// Cleared once the game is over or broken.
+    ticking: bool,
+    /// In lockstep, set from sending a tick until the game has drawn it. Also
+    /// set at the start, so the first tick waits for the game's opening frame.
+    awaiting_frame: bool,
+    signal: Option<i32>,
+}
+
+impl GameLoop<'_> {
+    fn run(&mut self, events: &Receiver<Event>) -> Result<()> {
+        loop {
+            let event = match self.next_event(events) {
+                Some(event) => event,
+                None => return Ok(()),
+            };
+            if self.handle(event)? == Flow::Stop {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Waits for the next event, or until the next tick is due. `None` if every
+    /// event source has gone away.
+    fn next_event(&self, events: &Receiver<Event>) -> Option<Event> {
+        // Game time stands still while paused, so there's no tick to wait for
+        if !self.ticking || self.clock.is_paused() {
+            return events.recv().ok();
+        }
+        let mut due = self.scheduler.deadline();
+        if self.awaiting_frame {
+            // In lockstep the tick waits for the frame, but not forever
+            due += self.args.speed_curve.interval(due) * FRAME_PATIENCE;
+        }
+        let wait = due.saturating_sub(self.clock.elapsed());
+        match events.recv_timeout(wait) {
+            Ok(event) => Some(event),
+            Err(RecvTimeoutError::Timeout) => Some(Event::Tick),
+            Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+
+    fn handle(&mut self, event: Event) -> Result<Flow> {
+        match event {
+            Event::Tick => self.tick()?,
+            Event::Key(key) => self.key_pressed(&key)?,
+            // Like keys, moves wait for the game to be running
+            Event::BotMove(_) if self.clock.is_paused() || !self.ticking => {}
+            Event::BotMove(action) => self.perform(action)?,
+            Event::BotStopped(reason) => {
+                // The game carries on without the bot, so show why it stopped moving
+                self.renderer
+                    .set_message(Some(format!("The bot stopped: {}", reason)));
+                self.renderer.draw()?;
+            }
+            Event::OutputLine(line) => {
+                if let Some(frame) = self.parser.push_line(line) {
+                    return self.handle(Event::FrameComplete(frame));
+                }
+            }
+            Event::ErrorLine(line) => {
+                // Anything on stderr is treated as an error, so stop ticking a broken game
+                self.errors.push(line);
+                self.stop_ticking();
+            }
+            Event::FrameComplete(frame) => self.show_frame(frame)?,
+            Event::ChildExited => {
+                if let Some(frame) = std::mem::take(&mut self.parser).finish() {
+                    self.show_frame(frame)?;
+                }
+                return Ok(Flow::Stop);
+            }
+            Event::Signal(signal) => {
+                self.signal = Some(signal);
+                return Ok(Flow::Stop);
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn tick(&mut self) -> Result<()> {
+        if !self.ticking {
+            return Ok(());
+        }
+        if self.awaiting_frame {
+            self.frame_missed();
+        }
+        let elapsed = self.clock.elapsed();
+        if self.input.send_tick().is_err() {
+            // The game has stopped reading, so it's on its way out
+            self.stop_ticking();
+            return Ok(());
+        }
+        self.awaiting_frame = self.args.lockstep;
+        self.bot_frame_due = true;
+
+        let speed_curve = &self.args.speed_curve;
+        let interval = self.scheduler.tick_sent(elapsed, speed_curve);
+        let late_ticks = self.scheduler.stats().late_ticks;
+        if let Some(hud) = self.renderer.hud() {
+            hud.tick_sent(elapsed, interval, speed_curve.debug_values(elapsed));
+            hud.set_late_ticks(late_ticks);
+        }
+        self.renderer.draw()?;
+        Ok(())
+    }
+
+    fn key_pressed(&mut self, key: &Key) -> Result<()> {
+        match self.args.keymap.action(key) {
+            Some(Action::Pause) => {
+                let message = self
+                    .clock
+                    .toggle_pause()
+                    .then(|| format!("Paused, press {} to resume", keymap::key_name(key)));
+                self.renderer.set_message(message);
+                self.renderer.draw()?;
+            }
+            // Moving while paused would change the game without time passing, and
+            // the bot does all the moving when there is one
+            Some(action)
+                if action != Action::Quit && (self.clock.is_paused() || self.args.bot.is_some()) => {}
+            Some(action) => self.perform(action)?,
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Sends a move to the game, whether it came from a key or the bot.
+    fn perform(&mut self, action: Action) -> Result<()> {
+        if let Some(command) = action.command() {
+            if self.input.send_key(command).is_err() {
+                self.stop_ticking();
+                return Ok(());
+            }
+            if let Some(hud) = self.renderer.hud() {
+                hud.key_pressed();
+            }
+        }
+        if action == Action::Quit {
+            // Closing stdin as well makes sure the game ends, even if it ignores the quit
+            self.stop_ticking();
+            self.input.close();
+        }
+        Ok(())
+    }
+
+    fn show_frame(&mut self, frame: Frame) -> Result<()> {
+        self.output.score = frame.score.or(self.output.score);
+        self.output.frames_received += 1;
+        self.output.recent_frames.push(frame.clone());
+
+        if let Some(hud) = self.renderer.hud() {
+            hud.frame_received(frame.score);
+        }
+        self.renderer.set_frame(&frame);
+        if std::mem::take(&mut self.awaiting_frame) {
+            self.frame_awaited();
+        }
+        self.renderer.draw()?;
+
+        let bot_frame_due = std::mem::take(&mut self.bot_frame_due);
+        if let Some(bot) = &self.bot {
+            if bot_frame_due && !frame.game_over && !self.clock.is_paused() {
+                // The bot thread only goes away if the bot has stopped
+                let _ = bot.send((frame, self.input.ticks_sent));
+            }
+        }
+        Ok(())
+    }
+
+    /// Warns the first time the game, in lockstep, draws a tick too slowly for
+    /// the next one to go out on time.
+    fn frame_awaited(&mut self) {
+        let behind = self.clock.elapsed().saturating_sub(self.scheduler.deadline());
+        if self.input.ticks_sent == 0 || behind <= LATE_TOLERANCE {
+            return;
+        }
+        self.scheduler.held_back();
+        if self.scheduler.stats().held_back == 1 {
+            self.renderer.set_message(Some(
+                "The game can't keep up with this speed, so it's running slower".to_string(),
+            ));
+        }
+    }
+
+    /// Warns the first time the game, in lockstep, takes so long to finish a
+    /// frame that the next tick goes out without it.
+    fn frame_missed(&mut self) {
+        self.scheduler.frame_missed();
+        if self.scheduler.stats().frames_missed == 1 {
+            self.renderer.set_message(Some(
+                "No complete frame from the game, so ticks are going out without waiting".to_string(),
+            ));
+        }
+    }
+
+    fn stop_ticking(&mut self) {
+        self.ticking = false;
+        self.clock.stop();
+    }
+}
+
+pub fn run_game(args: &Args) -> Result<GameSummary> {
+    println!("Starting Railroad Runners...");
+
+    let bot = args.bot.as_ref().map(|bot| bot.build(args.seed)).transpose()?;
+    // A bot can play without a terminal, e.g. in CI
+    let terminal = match TerminalInput::start() {
+        Ok(terminal) => Some(terminal),
+        Err(_) if bot.is_some() => None,
+        Err(error) => return Err(error),
+    };
+
+    let mut game = args.backend.spawn(&args.file_name, true)?;
+    shutdown::game_started(args.seed, args.record.clone(), game.id());
+
+    let mut stdin = game.stdin.take().ok_or(Error::msg("No stdin"))?;
+    let stdout = game.stdout.take().ok_or(Error::msg("No stdout"))?;
+    let stderr = game.stderr.take();
+
+    let recorder = crate::create_recorder(args)?;
+
+    stdin.write_all(protocol::seed_line(args.seed).as_bytes())?;
+
+    let clock = GameClock::start();
+    let hud = Hud::new(args.seed, args.debug);
+    let renderer = Renderer::start(args.theme.clone(), Some(hud))?;
+
+    let (sender, events) = mpsc::channel();
+    shutdown::forward_signals(sender.clone());
+    let keys_done = AtomicBool::new(false);
+
+    let (result, game_loop) = thread::scope(|scope| {
+        let output_sender = sender.clone();
+        scope.spawn(move || read_output(stdout, output_sender));
+        if let Some(stderr) = stderr {
+            let error_sender = sender.clone();
+            scope.spawn(move || read_errors(stderr, error_sender));
+        }
+
+        // Keys are read while a bot plays too, so it can still be paused or quit
+        if let Some(terminal) = &terminal {
+            let key_sender = sender.clone();
+            let keys_done = &keys_done;
+            scope.spawn(move || read_keys(terminal, keys_done, key_sender));
+        }
+        let bot_process = bot.as_ref().and_then(|bot| bot.process());
+        let bot_frames = bot.map(|bot| {
+            let (frames, frame_receiver) = mpsc::channel();
+            let bot_sender = sender.clone();
+            let clock = &clock;
+            scope.spawn(move || run_bot(bot, frame_receiver, clock, bot_sender));
+            frames
+        });
+
+        let mut game_loop = GameLoop {
+            args,
+            clock: &clock,
+            input: GameInput {
+                stdin: Some(stdin),
+                recorder,
+                clock: &clock,
+                ticks_sent: 0,
+                recent_keys: Recent::new(postmortem::RECENT_KEYS),
+            },
+            renderer,
+            parser: FrameParser::default(),
+            output: GameOutput {
+                score: None,
+                frames_received: 0,
+                recent_frames: Recent::new(postmortem::RECENT_FRAMES),
+            },
+            errors: Vec::new(),
+            bot: bot_frames,
+            bot_frame_due: false,
+            scheduler: TickScheduler::default(),
+            ticking: true,
+            awaiting_frame: args.lockstep,
+            signal: None,
+        };
+        let result = game_loop.run(&events);
+
+        // Wind down the threads: the game's output ends once it's gone, and the
+        // bot's thread once it stops getting frames
+        keys_done.store(true, Ordering::Relaxed);
+        game_loop.bot = None;
+        // A bot's program might never answer its last frame
+        if let Some(process) = &bot_process {
+            process.kill();
+        }
+        game_loop.input.close();
+        if result.is_err() || game_loop.signal.is_some() {
+            game.kill();
+        }
+        (result, game_loop)
+    });
+    result?;
+
+    let GameLoop {
+        input,
+        renderer,
+        output,
+        mut errors,
+        mut signal,
+        scheduler,
+        ..
+    } = game_loop;
+    clock.stop();
+    renderer.finish()?;
+    drop(terminal);
+    let status = game.wait()?;
+    shutdown::game_finished();
+    // The game can exit before everything it printed to stderr has been
+    // handled, and a Ctrl-C can reach the game and the wrapper at once
+    for event in events.try_iter() {
+        match event {
+            Event::ErrorLine(line) => errors.push(line),
+            Event::Signal(caught) => signal = signal.or(Some(caught)),
+            _ => {}
+        }
+    }
+
+    Ok(GameSummary {
+        score: output.score,
+        duration: clock.elapsed(),
+        status,
+        signal,
+        tick_stats: scheduler.stats().clone(),
+        post_mortem: (!errors.is_empty()).then_some(PostMortem {
+            errors,
+            ticks_sent: input.ticks_sent,
+            frames_received: output.frames_received,
+            frames: output.recent_frames,
+            keys: input.recent_keys,
+        }),
+    })
+}
+
+fn read_output(stdout: GameStdout, events: Sender<Event>) {
+    for line in frame::output_lines(BufReader::new(stdout)) {
+        if events.send(Event::OutputLine(line)).is_err() {
+            return;
+        }
+    }
+    let _ = events.send(Event::ChildExited);
+}
+
+fn read_errors(stderr: GameStderr, events: Sender<Event>) {
+    for line in frame::output_lines(BufReader::new(stderr)) {
+        if events.send(Event::ErrorLine(line)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Reads keys until `done` is set. Waiting for a key times out regularly, so
+/// this never holds things up once the game has ended.
+fn read_keys(terminal: &TerminalInput, done: &AtomicBool, events: Sender<Event>) {
+    let term = Term::stdout();
+    while !done.load(Ordering::Relaxed) {
+        if !terminal.wait_for_key(KEY_POLL_INTERVAL).unwrap() {
+            continue;
+        }
+        let key = match term.read_key() {
+            Ok(key) => key,
+            // Ctrl-C, which `console` turns into a SIGINT for the signal handler to deal with
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => panic!("Failed to read a key: {}", error),
+        };
+        if events.send(Event::Key(key)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Lets the bot choose a move after each tick. Bots can be slow programs, so
+/// they get a thread of their own rather than holding up the game.
+fn run_bot(
+    mut bot: Box<dyn Bot>,
+    frames: Receiver<(Frame, u64)>,
+    clock: &GameClock,
+    events: Sender<Event>,
+) {
+    for (frame, tick) in frames {
+        let event = match bot.choose(&frame, tick, clock.elapsed()) {
+            Ok(Some(action)) => Event::BotMove(action),
+            Ok(None) => continue,
+            Err(error) => {
+                let _ = events.send(Event::BotStopped(format!("{:#}", error)));
+                return;
+            }
+        };
+        if events.send(event).is_err() {
+            return;
+        }
+    }
+}
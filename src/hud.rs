@@ -0,0 +1,98 @@
+//! The status bar shown under the board.
+
+use console::Style;
+use std::time::{Duration, Instant};
+
+pub struct Hud {
+    seed: i32,
+    debug: bool,
+    elapsed: Duration,
+    interval: Duration,
+    ticks_sent: u64,
+    keys_pressed: u64,
+    score: Option<i32>,
+    curve_values: String,
+    last_tick_at: Option<Instant>,
+    latency: Option<Duration>,
+    late_ticks: u64,
+}
+
+impl Hud {
+    pub fn new(seed: i32, debug: bool) -> Self {
+        Hud {
+            seed,
+            debug,
+            elapsed: Duration::ZERO,
+            interval: Duration::ZERO,
+            ticks_sent: 0,
+            keys_pressed: 0,
+            score: None,
+            curve_values: String::new(),
+            last_tick_at: None,
+            latency: None,
+            late_ticks: 0,
+        }
+    }
+
+    pub fn tick_sent(&mut self, elapsed: Duration, interval: Duration, curve_values: String) {
+        self.ticks_sent += 1;
+        self.elapsed = elapsed;
+        self.interval = interval;
+        self.curve_values = curve_values;
+        self.last_tick_at = Some(Instant::now());
+    }
+
+    /// How many ticks so far went out later than scheduled.
+    pub fn set_late_ticks(&mut self, late_ticks: u64) {
+        self.late_ticks = late_ticks;
+    }
+
+    pub fn key_pressed(&mut self) {
+        self.keys_pressed += 1;
+    }
+
+    /// Records a frame arriving, which is how the pipe latency is measured:
+    /// the time from sending a tick to the game finishing the redraw it caused.
+    pub fn frame_received(&mut self, score: Option<i32>) {
+        if score.is_some() {
+            self.score = score;
+        }
+        if let Some(sent) = self.last_tick_at.take() {
+            self.latency = Some(sent.elapsed());
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let seconds = self.elapsed.as_secs();
+        let mut status = format!(
+            " Time {:02}:{:02} | Tick {:.2}s | Ticks {} | Keys {} | Seed {}",
+            seconds / 60,
+            seconds % 60,
+            self.interval.as_secs_f64(),
+            self.ticks_sent,
+            self.keys_pressed,
+            self.seed
+        );
+        if let Some(score) = self.score {
+            status.push_str(&format!(" | Score {}", score));
+        }
+        status.push(' ');
+
+        let mut lines = vec![Style::new().reverse().apply_to(status).to_string()];
+        if self.debug {
+            let latency = match self.latency {
+                Some(latency) => format!("{:.1}ms", latency.as_secs_f64() * 1000.0),
+                None => "-".to_string(),
+            };
+            lines.push(format!(
+                " elapsed {:.3}s | interval {:.4}s | {} | pipe latency {} | late ticks {}",
+                self.elapsed.as_secs_f64(),
+                self.interval.as_secs_f64(),
+                self.curve_values,
+                latency,
+                self.late_ticks
+            ));
+        }
+        lines
+    }
+}
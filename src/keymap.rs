@@ -0,0 +1,361 @@
+//! Maps keys pressed in the terminal to the commands the game understands.
+
+use anyhow::{bail, Context, Result};
+use console::Key;
+use serde::Deserialize;
+use std::{collections::HashMap, fmt, fs, path::Path, str::FromStr};
+
+/// A game command, and the character the MIPS program reads for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Jump,
+    Left,
+    Crouch,
+    Right,
+    Quit,
+    /// Handled by the wrapper itself rather than sent to the game.
+    Pause,
+}
+
+impl Action {
+    pub const ALL: [Action; 6] = [
+        Action::Jump,
+        Action::Left,
+        Action::Crouch,
+        Action::Right,
+        Action::Quit,
+        Action::Pause,
+    ];
+
+    /// The character sent to the game for this action, if any.
+    pub fn command(self) -> Option<char> {
+        match self {
+            Action::Jump => Some('w'),
+            Action::Left => Some('a'),
+            Action::Crouch => Some('s'),
+            Action::Right => Some('d'),
+            Action::Quit => Some('q'),
+            Action::Pause => None,
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Action::Jump => "jump",
+            Action::Left => "left",
+            Action::Crouch => "crouch",
+            Action::Right => "right",
+            Action::Quit => "quit",
+            Action::Pause => "pause",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Action {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Action::ALL
+            .into_iter()
+            .find(|action| action.to_string() == s)
+            .with_context(|| format!("Unknown action '{}'", s))
+    }
+}
+
+/// The built-in layouts. Every preset also accepts the arrow keys for movement
+/// and `p` to pause.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Preset {
+    /// w/a/s/d, q to quit.
+    #[default]
+    Wasd,
+    /// k/h/j/l, q to quit.
+    Vim,
+    /// z/q/s/d for AZERTY keyboards, Esc to quit.
+    Azerty,
+}
+
+impl Preset {
+    fn bindings(self) -> Vec<(Action, Vec<&'static str>)> {
+        let (jump, left, crouch, right, quit) = match self {
+            Preset::Wasd => ("w", "a", "s", "d", "q"),
+            Preset::Vim => ("k", "h", "j", "l", "q"),
+            Preset::Azerty => ("z", "q", "s", "d", "esc"),
+        };
+        vec![
+            (Action::Jump, vec![jump, "up"]),
+            (Action::Left, vec![left, "left"]),
+            (Action::Crouch, vec![crouch, "down"]),
+            (Action::Right, vec![right, "right"]),
+            (Action::Quit, vec![quit]),
+            (Action::Pause, vec!["p"]),
+        ]
+    }
+}
+
+impl FromStr for Preset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        <Preset as clap::ValueEnum>::from_str(s, true)
+            .map_err(|_| anyhow::anyhow!("Unknown keymap preset '{}'", s))
+    }
+}
+
+/// A `--bind action=key,key` override, replacing every key bound to `action`.
+#[derive(Clone, Debug)]
+pub struct Binding {
+    pub action: Action,
+    pub keys: Vec<Key>,
+}
+
+impl FromStr for Binding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (action, keys) = s
+            .split_once('=')
+            .context("Expected a binding like jump=w,up")?;
+        Ok(Binding {
+            action: action.trim().parse()?,
+            keys: parse_keys(keys.split(','))?,
+        })
+    }
+}
+
+/// A keymap file, e.g.
+///
+/// ```toml
+/// preset = "vim"
+/// jump = ["k", "space", "up"]
+/// quit = ["esc"]
+/// ```
+///
+/// Actions that are left out keep the preset's keys.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct KeymapConfig {
+    pub preset: Option<String>,
+    pub jump: Option<Vec<String>>,
+    pub left: Option<Vec<String>>,
+    pub crouch: Option<Vec<String>>,
+    pub right: Option<Vec<String>>,
+    pub quit: Option<Vec<String>>,
+    pub pause: Option<Vec<String>>,
+}
+
+impl KeymapConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read keymap file {}", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("Invalid keymap file {}", path.display()))
+    }
+
+    fn bindings(&self) -> Result<Vec<Binding>> {
+        let actions = [
+            (Action::Jump, &self.jump),
+            (Action::Left, &self.left),
+            (Action::Crouch, &self.crouch),
+            (Action::Right, &self.right),
+            (Action::Quit, &self.quit),
+            (Action::Pause, &self.pause),
+        ];
+        actions
+            .into_iter()
+            .filter_map(|(action, keys)| keys.as_ref().map(|keys| (action, keys)))
+            .map(|(action, keys)| {
+                Ok(Binding {
+                    action,
+                    keys: parse_keys(keys.iter().map(String::as_str))?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    actions: HashMap<Key, Action>,
+}
+
+impl Keymap {
+    /// Builds a keymap from a preset, then the keymap file, then `--bind`
+    /// overrides, each replacing the keys of the actions it mentions.
+    pub fn build(
+        preset: Option<Preset>,
+        config: Option<&KeymapConfig>,
+        overrides: &[Binding],
+    ) -> Result<Self> {
+        let config_preset = match config.and_then(|config| config.preset.as_deref()) {
+            Some(preset) => Some(preset.parse()?),
+            None => None,
+        };
+        let preset = preset.or(config_preset).unwrap_or_default();
+
+        let mut keys: Vec<(Action, Vec<Key>)> = preset
+            .bindings()
+            .into_iter()
+            .map(|(action, names)| Ok((action, parse_keys(names)?)))
+            .collect::<Result<_>>()?;
+
+        let config_bindings = match config {
+            Some(config) => config.bindings()?,
+            None => Vec::new(),
+        };
+        for binding in config_bindings.iter().chain(overrides) {
+            if let Some((_, bound)) = keys.iter_mut().find(|(action, _)| *action == binding.action) {
+                *bound = binding.keys.clone();
+            }
+        }
+
+        let mut actions = HashMap::new();
+        for (action, bound) in keys {
+            if bound.is_empty() {
+                bail!("No keys are bound to {}", action);
+            }
+            for key in bound {
+                if let Some(existing) = actions.insert(key.clone(), action) {
+                    if existing != action {
+                        bail!(
+                            "Key {} is bound to both {} and {}",
+                            key_name(&key),
+                            existing,
+                            action
+                        );
+                    }
+                }
+            }
+        }
+        Ok(Keymap { actions })
+    }
+
+    pub fn action(&self, key: &Key) -> Option<Action> {
+        self.actions.get(key).copied()
+    }
+}
+
+fn parse_keys<'a>(names: impl IntoIterator<Item = &'a str>) -> Result<Vec<Key>> {
+    names.into_iter().map(parse_key).collect()
+}
+
+/// Parses a key name: a single character, or one of the named keys below.
+pub fn parse_key(name: &str) -> Result<Key> {
+    let name = name.trim();
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(Key::Char(c));
+    }
+
+    let key = match name.to_lowercase().as_str() {
+        "up" | "arrowup" => Key::ArrowUp,
+        "down" | "arrowdown" => Key::ArrowDown,
+        "left" | "arrowleft" => Key::ArrowLeft,
+        "right" | "arrowright" => Key::ArrowRight,
+        "space" => Key::Char(' '),
+        "enter" => Key::Enter,
+        "tab" => Key::Tab,
+        "esc" | "escape" => Key::Escape,
+        "backspace" => Key::Backspace,
+        _ => bail!("Unknown key '{}'", name),
+    };
+    Ok(key)
+}
+
+pub fn key_name(key: &Key) -> String {
+    match key {
+        Key::ArrowUp => "up".to_string(),
+        Key::ArrowDown => "down".to_string(),
+        Key::ArrowLeft => "left".to_string(),
+        Key::ArrowRight => "right".to_string(),
+        Key::Char(' ') => "space".to_string(),
+        Key::Char(c) => format!("'{}'", c),
+        Key::Enter => "enter".to_string(),
+        Key::Tab => "tab".to_string(),
+        Key::Escape => "esc".to_string(),
+        Key::Backspace => "backspace".to_string(),
+        key => format!("{:?}", key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> KeymapConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn char_action(keymap: &Keymap, c: char) -> Option<Action> {
+        keymap.action(&Key::Char(c))
+    }
+
+    #[test]
+    fn parses_characters_and_named_keys() {
+        assert_eq!(parse_key("w").unwrap(), Key::Char('w'));
+        assert_eq!(parse_key(" ; ").unwrap(), Key::Char(';'));
+        assert_eq!(parse_key("Up").unwrap(), Key::ArrowUp);
+        assert_eq!(parse_key("arrowleft").unwrap(), Key::ArrowLeft);
+        assert_eq!(parse_key("space").unwrap(), Key::Char(' '));
+        assert_eq!(parse_key("ESCAPE").unwrap(), Key::Escape);
+        assert_eq!(parse_key("f1").unwrap_err().to_string(), "Unknown key 'f1'");
+    }
+
+    #[test]
+    fn presets_include_arrows_and_pause() {
+        let keymap = Keymap::build(Some(Preset::Vim), None, &[]).unwrap();
+        assert_eq!(char_action(&keymap, 'k'), Some(Action::Jump));
+        assert_eq!(char_action(&keymap, 'w'), None);
+        assert_eq!(keymap.action(&Key::ArrowUp), Some(Action::Jump));
+        assert_eq!(char_action(&keymap, 'p'), Some(Action::Pause));
+
+        let keymap = Keymap::build(Some(Preset::Azerty), None, &[]).unwrap();
+        assert_eq!(char_action(&keymap, 'q'), Some(Action::Left));
+        assert_eq!(keymap.action(&Key::Escape), Some(Action::Quit));
+    }
+
+    #[test]
+    fn file_overrides_preset_and_bind_overrides_file() {
+        let file = config("preset = \"vim\"\njump = [\"space\"]\ncrouch = [\"x\"]\n");
+        let bind: Binding = "crouch=c,down".parse().unwrap();
+        let keymap = Keymap::build(None, Some(&file), &[bind]).unwrap();
+        assert_eq!(char_action(&keymap, ' '), Some(Action::Jump));
+        // Each layer replaces every key of the actions it mentions
+        assert_eq!(char_action(&keymap, 'k'), None);
+        assert_eq!(keymap.action(&Key::ArrowUp), None);
+        assert_eq!(char_action(&keymap, 'x'), None);
+        assert_eq!(char_action(&keymap, 'c'), Some(Action::Crouch));
+        assert_eq!(char_action(&keymap, 'h'), Some(Action::Left));
+    }
+
+    #[test]
+    fn preset_flag_overrides_the_files_preset() {
+        let file = config("preset = \"vim\"\n");
+        let keymap = Keymap::build(Some(Preset::Wasd), Some(&file), &[]).unwrap();
+        assert_eq!(char_action(&keymap, 'w'), Some(Action::Jump));
+    }
+
+    #[test]
+    fn rejects_a_key_bound_to_two_actions() {
+        let bind: Binding = "jump=a".parse().unwrap();
+        let error = Keymap::build(None, None, &[bind]).unwrap_err();
+        assert_eq!(error.to_string(), "Key 'a' is bound to both jump and left");
+    }
+
+    #[test]
+    fn rejects_actions_without_keys() {
+        let file = config("quit = []\n");
+        let error = Keymap::build(None, Some(&file), &[]).unwrap_err();
+        assert_eq!(error.to_string(), "No keys are bound to quit");
+    }
+
+    #[test]
+    fn rejects_bad_bindings() {
+        assert!("jump".parse::<Binding>().is_err());
+        assert!("fly=w".parse::<Binding>().is_err());
+        assert!("jump=w,nope".parse::<Binding>().is_err());
+    }
+}
@@ -1,149 +1,838 @@
+mod backend;
+mod bot;
+mod clock;
+mod config;
+mod daily;
+mod date;
+mod diff;
+mod frame;
+mod game_loop;
+mod hud;
+mod keymap;
+mod mipsy;
+mod postmortem;
+mod protocol;
+mod reference;
+mod render;
+mod replay;
+mod scores;
+mod scheduler;
+mod script;
+mod shutdown;
+mod speed;
+mod terminal;
+mod theme;
+
 use anyhow::{Context, Error, Result};
-use clap::Parser;
-use console::Term;
-use rand;
-use std::time::SystemTime;
+use backend::{Backend, BackendKind, GameStdin, GameStdout};
+use bot::{BotConfig, Strategy};
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand};
+use clock::GameClock;
+use config::{Config, NamedSeed, SeedPolicy};
+use daily::DailyHistory;
+use date::{Date, DateTime};
+use diff::DiffArgs;
+use keymap::{Binding, Keymap, KeymapConfig, Preset};
+use frame::{Frame, FrameParser};
+use postmortem::{PostMortem, Recent, SentKey};
+use protocol::Input;
+use render::Renderer;
+use replay::{Recorder, Replay};
+use scores::{Leaderboard, ScoreEntry};
+use scheduler::TickStats;
+use script::Script;
+use speed::SpeedCurve;
+use theme::Theme;
+use std::time::{Duration, Instant};
 use std::{
-    io::{BufRead, BufReader, Write},
-    sync::{Arc, Mutex},
+    fs::{self, File},
+    io::{BufReader, Write},
+    path::PathBuf,
+    process::{ExitCode, ExitStatus},
+    sync::Mutex,
     thread,
 };
 
-// The higher the number, the slower the game
-const GAME_SPEED_MULTIPLIER: f64 = 1.2;
-const DEFAULT_MIPSY_PATH: &str = "/home/cs1521/bin/mipsy";
+const DEFAULT_THEME: &str = "classic";
+const DEFAULT_MAX_TICKS: u64 = 10_000;
+/// What runs are called when the reference game is played without a file name.
+const REFERENCE_FILE_NAME: &str = "reference";
 
 #[derive(Parser, Debug)]
-#[clap(name = "railroad-runners")]
+#[clap(
+    name = "railroad-runners",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Cli {
-    /// The file name of the railroad-runners assignment.
-    file_name: String,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    /// The path to the mipsy executable.
-    #[arg(long, value_name = "mipsy_path")]
-    mipsy_path: Option<String>,
+    /// The file name of the railroad-runners assignment. Not needed with `--backend reference`.
+    file_name: Option<String>,
+
+    #[command(flatten)]
+    backend: BackendArgs,
 
     /// Optional seed for the game. If omitted, a random seed will be used.
     #[arg(long, value_name = "seed")]
     seed: Option<i32>,
+
+    /// Play today's challenge. The seed and speed curve come from the UTC date,
+    /// so everyone playing today gets the same game.
+    #[arg(long, conflicts_with_all = ["seed", "speed_curve"])]
+    daily: bool,
+
+    /// Record the game to a replay file at this path.
+    #[arg(long, value_name = "path")]
+    record: Option<PathBuf>,
+
+    /// The built-in keymap to start from.
+    #[arg(long, value_enum, value_name = "preset")]
+    keymap: Option<Preset>,
+
+    /// A TOML file of key bindings, applied on top of the preset.
+    #[arg(long, value_name = "path")]
+    keymap_file: Option<PathBuf>,
+
+    /// Rebind an action, e.g. `--bind jump=space,up`. Can be repeated.
+    #[arg(long = "bind", value_name = "action=keys")]
+    bindings: Vec<Binding>,
+
+    /// How the time between ticks changes over the game. Intervals and times are in seconds.
+    ///
+    /// One of `log[:multiplier]` (the default, `log:1.2`), `constant:interval`,
+    /// `linear:start,end,duration`, `stepped:step:interval,interval,...`, or
+    /// `table:time=interval,...` (use `table:@path` to read `time interval` lines from a file).
+    #[arg(long, value_name = "curve")]
+    speed_curve: Option<SpeedCurve>,
+
+    /// The colour theme for the board: a built-in theme or the path to a theme file.
+    ///
+    /// The built-in themes are `classic` (the default), `plain` (no colours),
+    /// `unicode` (block and box-drawing characters), `colour-blind` and `high-contrast`.
+    #[arg(long, value_name = "theme")]
+    theme: Option<String>,
+
+    /// The name to put on the leaderboard. Defaults to your username.
+    #[arg(long, value_name = "name")]
+    name: Option<String>,
+
+    /// Don't add this game to the leaderboard or the daily history.
+    #[arg(long)]
+    no_save: bool,
+
+    /// Show the speed curve's internal values and the pipe latency under the status bar.
+    #[arg(long)]
+    debug: bool,
+
+    /// Wait for the game to finish drawing each tick before sending the next.
+    ///
+    /// A program too slow for the speed curve then just runs slower, instead of
+    /// ticks piling up in the pipe faster than it can draw them.
+    #[arg(long, conflicts_with = "headless")]
+    lockstep: bool,
+
+    /// Let a bot play instead of reading keys from the keyboard.
+    #[arg(long, value_enum, value_name = "strategy", conflicts_with = "headless")]
+    bot: Option<Strategy>,
+
+    /// Let a bot program play. It gets the frame drawn after each tick as a
+    /// line of JSON on stdin and answers each with a move, or an empty line, on stdout.
+    #[arg(long, value_name = "path", conflicts_with_all = ["bot", "headless"])]
+    bot_program: Option<PathBuf>,
+
+    /// An argument for `--bot-program`. Can be repeated.
+    #[arg(long = "bot-arg", value_name = "arg", requires = "bot_program", allow_hyphen_values = true)]
+    bot_args: Vec<String>,
+
+    /// Run without a terminal, sending ticks as fast as the game reads them and keys from `--script`.
+    #[arg(long)]
+    headless: bool,
+
+    /// The input script for `--headless`, or `-` to read it from stdin. Without one, only ticks are sent.
+    #[arg(long, value_name = "path", requires = "headless")]
+    script: Option<PathBuf>,
+
+    /// Where `--headless` writes the game's output. Defaults to stdout.
+    #[arg(long, value_name = "path", requires = "headless")]
+    output: Option<PathBuf>,
+
+    /// Quit a `--headless` game that is still running after this many ticks. Defaults to 10000.
+    #[arg(long, value_name = "ticks")]
+    max_ticks: Option<u64>,
+}
+
+/// What runs the game.
+#[derive(clap::Args, Debug)]
+struct BackendArgs {
+    /// What runs the assignment file: mipsy (the default), SPIM, or the file itself as a native executable.
+    #[arg(long, value_enum, value_name = "backend")]
+    backend: Option<BackendKind>,
+
+    /// The path to the mipsy executable.
+    #[arg(long, value_name = "mipsy_path")]
+    mipsy_path: Option<String>,
+
+    /// The path to the spim executable.
+    #[arg(long, value_name = "spim_path")]
+    spim_path: Option<String>,
+}
+
+impl BackendArgs {
+    /// The backend from the command line, otherwise the config file.
+    fn kind(&self, config: &Config) -> BackendKind {
+        self.backend.or(config.backend).unwrap_or_default()
+    }
+
+    fn build(&self, kind: BackendKind, config: &Config) -> Result<Backend> {
+        Backend::new(
+            kind,
+            self.mipsy_path.clone(),
+            config.mipsy_path.clone(),
+            self.spim_path.clone().or_else(|| config.spim_path.clone()),
+        )
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Replay a recorded game, sending the game exactly what it received originally.
+    Replay {
+        /// The replay file to play back.
+        replay_file: PathBuf,
+
+        /// The file name of the railroad-runners assignment. Defaults to the one in the replay.
+        #[arg(long, value_name = "file_name")]
+        file_name: Option<String>,
+
+        #[command(flatten)]
+        backend: BackendArgs,
+
+        /// Send every input immediately instead of at its recorded time.
+        #[arg(long)]
+        instant: bool,
+
+        /// The colour theme for the board. See the main `--theme` option.
+        #[arg(long, value_name = "theme")]
+        theme: Option<String>,
+    },
+
+    /// Run a student's program and a reference program on the same input and
+    /// report the first frame where their output differs.
+    Diff {
+        /// The student's railroad-runners assignment.
+        student: String,
+
+        /// The reference railroad-runners implementation.
+        reference: String,
+
+        #[command(flatten)]
+        backend: BackendArgs,
+
+        /// What runs the reference, if not the same as the student's program,
+        /// e.g. `native` for a compiled C solution.
+        #[arg(long, value_enum, value_name = "backend")]
+        reference_backend: Option<BackendKind>,
+
+        /// Optional seed for both games. If omitted, a random seed will be used.
+        #[arg(long, value_name = "seed")]
+        seed: Option<i32>,
+
+        /// An input script of `tick key` lines, as used by `--headless`.
+        #[arg(long, value_name = "path")]
+        script: Option<PathBuf>,
+
+        /// Quit both games if they are still running after this many ticks.
+        #[arg(long, value_name = "ticks", default_value_t = 1_000)]
+        max_ticks: u64,
+    },
+
+    /// List the best runs on the local leaderboard, overall and for each seed.
+    Scores {
+        /// Only show runs with this seed.
+        #[arg(long, value_name = "seed")]
+        seed: Option<i32>,
+
+        /// Only show runs of this exact assignment file.
+        #[arg(long, value_name = "file_name")]
+        file_name: Option<PathBuf>,
+
+        /// How many runs (and seeds) to show.
+        #[arg(long, value_name = "count", default_value_t = 10)]
+        limit: usize,
+    },
+
+    /// Show today's challenge and results, recent winners and everyone's streaks.
+    Daily {
+        /// How many days of history to show, including today.
+        #[arg(long, value_name = "days", default_value_t = 7)]
+        days: usize,
+    },
 }
 
 #[derive(Clone, Debug)]
 struct Args {
     file_name: String,
-    mipsy_path: String,
+    backend: Backend,
     seed: i32,
+    /// The day whose challenge is being played, for `--daily`.
+    daily: Option<Date>,
+    record: Option<PathBuf>,
+    keymap: Keymap,
+    speed_curve: SpeedCurve,
+    theme: Theme,
+    debug: bool,
+    lockstep: bool,
+    player: String,
+    save_score: bool,
+    bot: Option<BotConfig>,
+    headless: Option<HeadlessArgs>,
 }
 
-fn main() -> Result<()> {
-    let args = parse_args();
-    println!("Using seed {}", args.seed);
-    run_game(&args)?;
+/// How a game went, once it's over.
+struct GameSummary {
+    score: Option<i32>,
+    /// Time spent playing, not counting pauses.
+    duration: Duration,
+    status: ExitStatus,
+    /// The signal that stopped the game, if it was interrupted.
+    signal: Option<i32>,
+    tick_stats: TickStats,
+    /// What went wrong, if the game printed any errors.
+    post_mortem: Option<PostMortem>,
+}
 
-    println!("Game finished! Seed was {}", args.seed);
-    Ok(())
+/// What the game printed to stdout, once it's finished.
+struct GameOutput {
+    /// The last score printed.
+    score: Option<i32>,
+    frames_received: usize,
+    recent_frames: Recent<Frame>,
 }
 
-fn print_thread(stdout: BufReader<&mut std::process::ChildStdout>) {
-    for line in stdout.lines() {
-        println!("{}", line.unwrap());
-    }
+#[derive(Clone, Debug)]
+struct HeadlessArgs {
+    script: Script,
+    output: Option<PathBuf>,
+    max_ticks: u64,
 }
 
-fn tick_thread(
-    game_thread: Arc<Mutex<thread::ScopedJoinHandle<()>>>,
-    stdin: Arc<Mutex<&mut std::process::ChildStdin>>,
-    start_time: &SystemTime,
-) {
-    loop {
-        if game_thread.lock().unwrap().is_finished() {
-            break;
-        }
+#[derive(Clone, Debug)]
+struct ReplayArgs {
+    replay: Replay,
+    file_name: String,
+    backend: Backend,
+    instant: bool,
+    theme: Theme,
+}
+
+enum Mode {
+    Play(Args),
+    Replay(ReplayArgs),
+    Diff(DiffArgs),
+    Scores {
+        seed: Option<i32>,
+        file_name: Option<PathBuf>,
+        limit: usize,
+    },
+    Daily {
+        days: usize,
+    },
+}
 
-        stdin.lock().unwrap().write_all(b"\'\n").unwrap();
+/// Everything that writes to the game's stdin goes through here, so the
+/// recording always matches what the game actually received.
+struct GameInput<'a> {
+    /// Gone once closed.
+    stdin: Option<GameStdin>,
+    recorder: Option<Recorder>,
+    clock: &'a GameClock,
+    ticks_sent: u64,
+    recent_keys: Recent<SentKey>,
+}
 
-        let now = SystemTime::now();
-        let elapsed = now.duration_since(*start_time).unwrap().as_secs_f64();
+impl GameInput<'_> {
+    fn send_tick(&mut self) -> Result<()> {
+        let Some(stdin) = &mut self.stdin else {
+            return Ok(());
+        };
+        stdin.write_all(Input::Tick.encode().as_bytes())?;
+        if let Some(recorder) = &mut self.recorder {
+            recorder.tick(self.ticks_sent, self.clock.elapsed())?;
+        }
+        self.ticks_sent += 1;
+        Ok(())
+    }
 
-        const BASE: f64 = 10.0;
-        let num = f64::log(if elapsed < BASE { BASE } else { elapsed }, BASE);
-        let time_to_sleep = f64::min(1.0, GAME_SPEED_MULTIPLIER / num);
+    fn send_key(&mut self, key: char) -> Result<()> {
+        let Some(stdin) = &mut self.stdin else {
+            return Ok(());
+        };
+        stdin.write_all(Input::Key(key).encode().as_bytes())?;
+        if let Some(recorder) = &mut self.recorder {
+            recorder.key(key, self.clock.elapsed())?;
+        }
+        self.recent_keys.push(SentKey {
+            key,
+            tick: self.ticks_sent,
+        });
+        Ok(())
+    }
 
-        print!("{}[2J", 27 as char);    // clear the terminal
-        println!("num: {}", num);
-        println!("tts: {}", time_to_sleep);
-        std::thread::sleep(std::time::Duration::from_secs_f64(time_to_sleep));
+    /// Closes the game's stdin, so it sees the end of its input. Anything sent
+    /// afterwards is thrown away without being recorded.
+    fn close(&mut self) {
+        self.stdin = None;
     }
-    println!("Press any key to exit");
 }
 
-fn input_thread(
-    game_thread: Arc<Mutex<thread::ScopedJoinHandle<()>>>,
-    stdin: Arc<Mutex<&mut std::process::ChildStdin>>,
-) {
-    const VALID_CHARS: &[char] = &['w', 'a', 's', 'd', 'q'];
+fn main() -> Result<ExitCode> {
+    shutdown::install()?;
+    let mut exit_code = ExitCode::SUCCESS;
+    match parse_args()? {
+        Mode::Play(args) => {
+            // Headless output can go to stdout, so keep the wrapper's own messages out of it
+            let report = |message: String| match args.headless {
+                Some(_) => eprintln!("{}", message),
+                None => println!("{}", message),
+            };
+
+            if let Some(date) = args.daily {
+                report(format!("Daily challenge for {}", date));
+            }
+            report(format!("Using seed {}", args.seed));
+            report(format!("Running the game with {}", args.backend.describe()));
+            match &args.headless {
+                Some(headless) => {
+                    let status = run_headless(&args, headless)?;
+                    if !status.success() {
+                        report(format!("The game exited with {}", status));
+                        exit_code = status_exit_code(status);
+                    }
+                }
+                None => {
+                    println!("Using speed curve {}", args.speed_curve);
+                    let summary = game_loop::run_game(&args)?;
+                    if let Some(post_mortem) = &summary.post_mortem {
+                        post_mortem.print();
+                    }
+                    if let Some(signal) = summary.signal {
+                        println!("Stopped by {}", shutdown::signal_name(signal));
+                        exit_code = ExitCode::from(shutdown::signal_exit_code(signal));
+                    } else if !summary.status.success() {
+                        println!("The game exited with {}", summary.status);
+                        exit_code = status_exit_code(summary.status);
+                    }
+                    println!("{}", summary.tick_stats);
+                    if let Some(score) = summary.score {
+                        println!("Final score: {}", score);
+                        // An interrupted game didn't get the chance to finish, and a
+                        // bot's score isn't the player's
+                        if args.save_score && summary.signal.is_none() && args.bot.is_none() {
+                            save_score(&args, score, summary.duration);
+                        }
+                    }
+                }
+            }
 
-    loop {
-        let term = Term::stdout();
-        if game_thread.lock().unwrap().is_finished() {
-            break;
+            report(format!("Game finished! Seed was {}", args.seed));
+            if let Some(record) = &args.record {
+                report(format!("Replay saved to {}", record.display()));
+            }
         }
+        Mode::Replay(args) => {
+            println!("Replaying seed {}", args.replay.seed);
+            println!("Running the game with {}", args.backend.describe());
+            run_replay(&args)?;
 
-        let input = term.read_char().unwrap();
-        if VALID_CHARS.contains(&input) {
-            print!("{}[2J", 27 as char);    // clear the terminal
-            if let Err(_) = stdin
-                .lock()
-                .unwrap()
-                .write_all(format!("{}\n", input).as_bytes())
-            {
-                break;
+            println!("Replay finished! Seed was {}", args.replay.seed);
+        }
+        Mode::Diff(args) => {
+            println!("Using seed {}", args.seed);
+            println!("Running the student's program with {}", args.student_backend.describe());
+            println!("Running the reference with {}", args.reference_backend.describe());
+            if !diff::run_diff(&args)? {
+                return Ok(ExitCode::FAILURE);
             }
         }
+        Mode::Scores {
+            seed,
+            file_name,
+            limit,
+        } => Leaderboard::load()?.print(seed, file_name.as_deref(), limit)?,
+        Mode::Daily { days } => DailyHistory::load()?.print(Date::today(), days),
+    }
+    Ok(exit_code)
+}
+
+/// Adds a finished game to the leaderboard, and to the daily history for
+/// `--daily`. A failure here shouldn't lose the rest of the game's output, so
+/// it's only reported.
+fn save_score(args: &Args, score: i32, duration: Duration) {
+    let entry = match args.backend.program_hash(&args.file_name) {
+        Ok(hash) => ScoreEntry::new(score, args.seed, duration, &args.player, &args.file_name, hash),
+        Err(error) => return eprintln!("Couldn't save your score: {:#}", error),
+    };
+    if let Some(date) = args.daily {
+        match DailyHistory::record(date, &entry) {
+            Ok(result) => println!(
+                "Daily challenge: #{} today, {} day streak",
+                result.rank, result.streak
+            ),
+            Err(error) => eprintln!("Couldn't save your daily result: {:#}", error),
+        }
+    }
+    match Leaderboard::add(entry) {
+        Ok(rank) => println!("Saved to the leaderboard at #{}", rank),
+        Err(error) => eprintln!("Couldn't save your score: {:#}", error),
     }
 }
 
-fn run_game(args: &Args) -> Result<()> {
-    println!("Starting Railroad Runners...");
+/// Shows the game's output as it arrives.
+fn print_thread(stdout: BufReader<&mut GameStdout>, renderer: &Mutex<Renderer>) {
+    let mut parser = FrameParser::default();
+    for line in frame::output_lines(stdout) {
+        if let Some(frame) = parser.push_line(line) {
+            show_frame(&frame, renderer);
+        }
+    }
+    if let Some(frame) = parser.finish() {
+        show_frame(&frame, renderer);
+    }
+}
+
+fn show_frame(frame: &Frame, renderer: &Mutex<Renderer>) {
+    let mut renderer = renderer.lock().unwrap();
+    if let Some(hud) = renderer.hud() {
+        hud.frame_received(frame.score);
+    }
+    renderer.set_frame(frame);
+    renderer.draw().unwrap();
+}
+
+fn create_recorder(args: &Args) -> Result<Option<Recorder>> {
+    match &args.record {
+        Some(path) => Ok(Some(Recorder::create(path, &args.file_name, args.seed)?)),
+        None => Ok(None),
+    }
+}
+
+/// The wrapper's exit code for a game that exited with `status`: the same
+/// code, or a plain failure if it didn't exit normally.
+fn status_exit_code(status: ExitStatus) -> ExitCode {
+    status
+        .code()
+        .map_or(ExitCode::FAILURE, |code| ExitCode::from(code as u8))
+}
+
+/// Runs the game without touching the terminal.
+fn run_headless(args: &Args, headless: &HeadlessArgs) -> Result<ExitStatus> {
+    let mut output: Box<dyn Write + Send> = match &headless.output {
+        Some(path) => Box::new(
+            File::create(path)
+                .with_context(|| format!("Failed to create output file {}", path.display()))?,
+        ),
+        None => Box::new(std::io::stdout()),
+    };
+    let recorder = create_recorder(args)?;
+
+    shutdown::scripted_game_started(args.seed, args.record.clone());
+    let result = run_scripted(
+        &args.backend,
+        &args.file_name,
+        args.seed,
+        &headless.script.inputs(headless.max_ticks),
+        recorder,
+        &mut output,
+    );
+    shutdown::game_finished();
+    result
+}
 
-    let mut child = std::process::Command::new(&args.mipsy_path)
-        .arg(&args.file_name)
-        .stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::piped())
-        .spawn().context("Failed to spawn mipsy. Try providing the path to mipsy via --mipsy-path /path/to/mipsy.")?;
+/// Plays a fixed sequence of inputs into the game, copying everything it
+/// prints to `output`. The pipe to the game provides the pacing: each write
+/// blocks until the game has caught up. Returns how the game exited.
+pub(crate) fn run_scripted(
+    backend: &Backend,
+    file_name: &str,
+    seed: i32,
+    inputs: &[Input],
+    recorder: Option<Recorder>,
+    output: &mut (dyn Write + Send),
+) -> Result<ExitStatus> {
+    let mut game = backend.spawn(file_name, false)?;
+    let pid = game.id();
+    shutdown::process_started(pid);
 
-    let stdin = child.stdin.as_mut().ok_or(Error::msg("No stdin"))?;
-    let stdout = BufReader::new(child.stdout.as_mut().ok_or(Error::msg("No stdout"))?);
+    let mut stdin = game.stdin.take().ok_or(Error::msg("No stdin"))?;
+    let mut stdout = game.stdout.take().ok_or(Error::msg("No stdout"))?;
 
-    stdin.write_all(format!("{}\n", args.seed).as_bytes())?;
+    stdin.write_all(protocol::seed_line(seed).as_bytes())?;
 
-    let start_time = SystemTime::now();
+    let clock = GameClock::start();
 
-    thread::scope(|scope| {
-        // Grabs output from the game
-        let handle = Arc::new(Mutex::new(scope.spawn(|| print_thread(stdout))));
+    let result = thread::scope(|scope| {
+        let output_thread = scope.spawn(|| std::io::copy(&mut stdout, output));
 
-        let stdin = Arc::new(Mutex::new(stdin));
-        let stdin_mutex = stdin.clone();
-        let game_thread = handle.clone();
-        // Tick thread, advances the game state every so often
-        scope.spawn(|| tick_thread(game_thread, stdin_mutex, &start_time));
+        let mut input = GameInput {
+            stdin: Some(stdin),
+            recorder,
+            clock: &clock,
+            ticks_sent: 0,
+            recent_keys: Recent::new(postmortem::RECENT_KEYS),
+        };
+        let fed = inputs.iter().try_for_each(|next| match next {
+            Input::Tick => input.send_tick(),
+            Input::Key(key) => input.send_key(*key),
+        });
+        // Close stdin so the game can't block waiting for more input
+        drop(input);
 
-        let game_thread = handle.clone();
-        let stdin_mutex = stdin.clone();
-        // Input thread, listens for user input
-        scope.spawn(|| input_thread(game_thread, stdin_mutex));
+        output_thread.join().unwrap()?;
+        fed
     });
 
-    child.wait()?;
+    let status = game.wait()?;
+    shutdown::process_finished(pid);
+    match result {
+        Err(error) if is_broken_pipe(&error) => Ok(status),
+        result => result.map(|()| status),
+    }
+}
+
+/// Feeds the recorded inputs to the game in their original order, waiting
+/// until each one's recorded offset unless `--instant` was given.
+fn replay_thread(mut stdin: GameStdin, args: &ReplayArgs) -> Result<()> {
+    stdin.write_all(protocol::seed_line(args.replay.seed).as_bytes())?;
+
+    let start_time = Instant::now();
+    for event in &args.replay.events {
+        if !args.instant {
+            if let Some(wait) = event.offset.checked_sub(start_time.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+        stdin.write_all(event.input.encode().as_bytes())?;
+    }
+
+    // Dropping stdin closes the pipe, so the game sees EOF once the replay runs out
     Ok(())
 }
 
-fn parse_args() -> Args {
-    let args = Cli::parse();
-    Args {
-        file_name: args.file_name,
-        mipsy_path: args.mipsy_path.unwrap_or(DEFAULT_MIPSY_PATH.to_string()),
-        seed: args.seed.unwrap_or_else(|| rand::random()),
+fn run_replay(args: &ReplayArgs) -> Result<()> {
+    let mut game = args.backend.spawn(&args.file_name, false)?;
+    shutdown::game_started(args.replay.seed, None, game.id());
+
+    let stdin = game.stdin.take().ok_or(Error::msg("No stdin"))?;
+    let stdout = BufReader::new(game.stdout.as_mut().ok_or(Error::msg("No stdout"))?);
+
+    let renderer = Mutex::new(Renderer::start(args.theme.clone(), None)?);
+
+    let result = thread::scope(|scope| {
+        scope.spawn(|| print_thread(stdout, &renderer));
+        scope.spawn(|| replay_thread(stdin, args)).join().unwrap()
+    });
+
+    renderer.into_inner().unwrap().finish()?;
+    game.wait()?;
+    shutdown::game_finished();
+    // The game quitting before the replay ends shows up as a broken pipe, which is fine
+    match result {
+        Err(error) if is_broken_pipe(&error) => Ok(()),
+        result => result,
     }
 }
+
+fn is_broken_pipe(error: &Error) -> bool {
+    error
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|error| error.kind() == std::io::ErrorKind::BrokenPipe)
+}
+
+/// Combines the command line with the config files. Options on the command
+/// line win, then the project's config, then the user's.
+fn parse_args() -> Result<Mode> {
+    let args = Cli::parse();
+    let config = Config::load()?;
+    let mode = match args.command {
+        Some(Command::Replay {
+            replay_file,
+            file_name,
+            backend,
+            instant,
+            theme,
+        }) => {
+            let replay = Replay::load(&replay_file)?;
+            Mode::Replay(ReplayArgs {
+                file_name: file_name.unwrap_or_else(|| replay.program.clone()),
+                backend: backend.build(backend.kind(&config), &config)?,
+                replay,
+                instant,
+                theme: load_theme(theme, &config)?,
+            })
+        }
+        Some(Command::Diff {
+            student,
+            reference,
+            backend,
+            reference_backend,
+            seed,
+            script,
+            max_ticks,
+        }) => {
+            let kind = backend.kind(&config);
+            let student_backend = backend.build(kind, &config)?;
+            // Only look for an interpreter again if the reference needs a different one
+            let reference_backend = match reference_backend {
+                Some(reference_kind) if reference_kind != kind => {
+                    backend.build(reference_kind, &config)?
+                }
+                _ => student_backend.clone(),
+            };
+            Mode::Diff(DiffArgs {
+                student,
+                reference,
+                student_backend,
+                reference_backend,
+                seed: match (seed, config.seed) {
+                    (Some(seed), _) | (None, Some(SeedPolicy::Fixed(seed))) => seed,
+                    _ => rand::random(),
+                },
+                script: match &script {
+                    Some(path) => Script::load(path)?,
+                    None => Script::default(),
+                },
+                max_ticks,
+            })
+        }
+        Some(Command::Scores {
+            seed,
+            file_name,
+            limit,
+        }) => Mode::Scores {
+            seed,
+            file_name,
+            limit,
+        },
+        Some(Command::Daily { days }) => Mode::Daily { days },
+        None => {
+            let preset = match (args.keymap, &config.keymap) {
+                (Some(preset), _) => Some(preset),
+                (None, Some(name)) => Some(name.parse().context("Invalid keymap in the config file")?),
+                (None, None) => None,
+            };
+            let keymap_config = match args.keymap_file.as_ref().or(config.keymap_file.as_ref()) {
+                Some(path) => Some(KeymapConfig::load(path)?),
+                None => None,
+            };
+            // Bindings on the command line come last, so they replace the config's
+            let mut bindings = config
+                .bind
+                .iter()
+                .flatten()
+                .map(|binding| binding.parse())
+                .collect::<Result<Vec<Binding>>>()
+                .context("Invalid bind in the config file")?;
+            bindings.extend(args.bindings);
+            let keymap = Keymap::build(preset, keymap_config.as_ref(), &bindings)
+                .context("Invalid key bindings")?;
+
+            // A seed or speed curve on the command line means this isn't the daily challenge
+            let config_daily = config.seed == Some(SeedPolicy::Named(NamedSeed::Daily))
+                && args.seed.is_none()
+                && args.speed_curve.is_none();
+
+            let speed_curve = match (args.speed_curve, &config.speed_curve) {
+                (Some(curve), _) => Some(curve),
+                (None, Some(curve)) => Some(
+                    curve
+                        .parse()
+                        .context("Invalid speed-curve in the config file")?,
+                ),
+                (None, None) => None,
+            };
+
+            let daily = (args.daily || config_daily).then(Date::today);
+            let seed = match (daily, args.seed, config.seed) {
+                (Some(date), _, _) => daily::seed(date),
+                (None, Some(seed), _) | (None, None, Some(SeedPolicy::Fixed(seed))) => seed,
+                _ => rand::random(),
+            };
+
+            let record = match (args.record, &config.record_dir) {
+                (Some(path), _) => Some(path),
+                (None, Some(dir)) => {
+                    fs::create_dir_all(dir).with_context(|| {
+                        format!("Failed to create the record directory {}", dir.display())
+                    })?;
+                    let name = format!("{}-seed-{}.replay", DateTime::now().file_stamp(), seed);
+                    Some(dir.join(name))
+                }
+                (None, None) => None,
+            };
+
+            let kind = args.backend.kind(&config);
+            let file_name = match (args.file_name, kind) {
+                (Some(file_name), _) => file_name,
+                (None, BackendKind::Reference) => REFERENCE_FILE_NAME.to_string(),
+                (None, _) => Cli::command()
+                    .error(
+                        ErrorKind::MissingRequiredArgument,
+                        "The assignment's file name is needed unless using --backend reference",
+                    )
+                    .exit(),
+            };
+
+            Mode::Play(Args {
+                file_name,
+                backend: args.backend.build(kind, &config)?,
+                seed,
+                daily,
+                record,
+                keymap,
+                speed_curve: match daily {
+                    Some(_) => daily::speed_curve(),
+                    None => speed_curve.unwrap_or_default(),
+                },
+                theme: load_theme(args.theme, &config)?,
+                debug: args.debug || config.debug.unwrap_or(false),
+                lockstep: args.lockstep || config.lockstep.unwrap_or(false),
+                player: args
+                    .name
+                    .or(config.name)
+                    .unwrap_or_else(scores::default_player),
+                save_score: !args.no_save && config.save_scores.unwrap_or(true),
+                bot: match (args.bot, args.bot_program) {
+                    (Some(strategy), _) => Some(BotConfig::BuiltIn(strategy)),
+                    (None, Some(program)) => Some(BotConfig::External {
+                        program,
+                        args: args.bot_args,
+                    }),
+                    (None, None) => None,
+                },
+                headless: match args.headless {
+                    true => Some(HeadlessArgs {
+                        script: match &args.script {
+                            Some(path) => Script::load(path)?,
+                            None => Script::default(),
+                        },
+                        output: args.output,
+                        max_ticks: args
+                            .max_ticks
+                            .or(config.max_ticks)
+                            .unwrap_or(DEFAULT_MAX_TICKS),
+                    }),
+                    false => None,
+                },
+            })
+        }
+    };
+    Ok(mode)
+}
+
+/// The theme from the command line, otherwise the config file, otherwise the default.
+fn load_theme(theme: Option<String>, config: &Config) -> Result<Theme> {
+    let name = theme.or_else(|| config.theme.clone());
+    Theme::load(name.as_deref().unwrap_or(DEFAULT_THEME))
+}
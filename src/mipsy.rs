@@ -0,0 +1,95 @@
+//! Finding a mipsy that works.
+//!
+//! Candidates are tried in order: `--mipsy-path`, the `RAILROAD_MIPSY`
+//! environment variable, `mipsy-path` in the config file, `mipsy` on the
+//! `PATH`, and the path on CSE machines.
+//! The first one whose `--version` runs successfully is used.
+
+use anyhow::{bail, Result};
+use std::{
+    env, fmt,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+/// Where mipsy lives on CSE machines.
+pub const DEFAULT_MIPSY_PATH: &str = "/home/cs1521/bin/mipsy";
+pub const MIPSY_ENV_VAR: &str = "RAILROAD_MIPSY";
+
+/// Where a mipsy path came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    Flag,
+    Environment,
+    Config,
+    Path,
+    CseDefault,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Flag => write!(f, "--mipsy-path"),
+            Source::Environment => write!(f, "${}", MIPSY_ENV_VAR),
+            Source::Config => write!(f, "the config file"),
+            Source::Path => write!(f, "the PATH"),
+            Source::CseDefault => write!(f, "the CSE default"),
+        }
+    }
+}
+
+/// Returns the first candidate that works and where it came from, warning
+/// about any that were skipped on the way.
+pub fn find(flag: Option<String>, config: Option<String>) -> Result<(String, Source)> {
+    let candidates = [
+        (flag, Source::Flag),
+        (env::var(MIPSY_ENV_VAR).ok(), Source::Environment),
+        (config, Source::Config),
+        (search_path(), Source::Path),
+        (Some(DEFAULT_MIPSY_PATH.to_string()), Source::CseDefault),
+    ];
+
+    let mut rejected = Vec::new();
+    for (path, source) in candidates {
+        let path = match path {
+            Some(path) => path,
+            None => continue,
+        };
+        match verify(&path) {
+            Ok(()) => {
+                for reason in &rejected {
+                    eprintln!("Skipped {}", reason);
+                }
+                return Ok((path, source));
+            }
+            Err(reason) => rejected.push(format!("mipsy from {} ({}): {}", source, path, reason)),
+        }
+    }
+    bail!(
+        "Couldn't find a working mipsy. Tried:\n  {}\nTry providing the path to mipsy via --mipsy-path /path/to/mipsy.",
+        rejected.join("\n  ")
+    )
+}
+
+fn search_path() -> Option<String> {
+    let path = env::var_os("PATH")?;
+    env::split_paths(&path)
+        .map(|dir| dir.join("mipsy"))
+        .find(|candidate| candidate.is_file())
+        .map(|candidate: PathBuf| candidate.display().to_string())
+}
+
+/// Checks that `path` runs and can report its version.
+fn verify(path: &str) -> Result<(), String> {
+    let status = Command::new(path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|error| error.to_string())?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("`--version` failed with {}", status)),
+    }
+}
@@ -0,0 +1,96 @@
+//! What to show when the game prints an error, such as a MIPS runtime error.
+//!
+//! The game's stderr is collected while it runs instead of being drawn over
+//! the board, and shown once the terminal is back to normal together with the
+//! last few frames and keys, so there's enough context to find the bug.
+
+use crate::frame::Frame;
+use std::collections::VecDeque;
+
+/// How many of the most recent frames are kept for the post-mortem.
+pub const RECENT_FRAMES: usize = 3;
+/// How many of the most recent keys are kept for the post-mortem.
+pub const RECENT_KEYS: usize = 10;
+
+/// A key sent to the game, and how many ticks had been sent before it.
+#[derive(Clone, Copy, Debug)]
+pub struct SentKey {
+    pub key: char,
+    pub tick: u64,
+}
+
+/// Keeps only the last `capacity` items pushed.
+#[derive(Clone, Debug)]
+pub struct Recent<T> {
+    items: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> Recent<T> {
+    pub fn new(capacity: usize) -> Self {
+        Recent {
+            items: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        if self.items.len() == self.capacity {
+            self.items.pop_front();
+        }
+        self.items.push_back(item);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+pub struct PostMortem {
+    /// Everything the game printed to stderr.
+    pub errors: Vec<String>,
+    pub ticks_sent: u64,
+    /// How many frames the game drew in total, including the recent ones.
+    pub frames_received: usize,
+    pub frames: Recent<Frame>,
+    pub keys: Recent<SentKey>,
+}
+
+impl PostMortem {
+    pub fn print(&self) {
+        println!();
+        println!("The game printed an error after {} ticks:", self.ticks_sent);
+        for line in &self.errors {
+            println!("    {}", line);
+        }
+
+        let first_frame = self.frames_received - self.frames.len() + 1;
+        for (number, frame) in (first_frame..).zip(self.frames.iter()) {
+            println!();
+            println!("Frame {}:", number);
+            for line in &frame.lines {
+                println!("    {}", line);
+            }
+        }
+
+        println!();
+        if self.keys.is_empty() {
+            println!("No keys were sent.");
+        } else {
+            let keys: Vec<String> = self
+                .keys
+                .iter()
+                .map(|sent| format!("{} after tick {}", sent.key, sent.tick))
+                .collect();
+            println!("Last keys sent: {}", keys.join(", "));
+        }
+    }
+}
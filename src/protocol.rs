@@ -0,0 +1,29 @@
+//! The stdin protocol spoken by the railroad-runners program: a seed on the
+//! first line, then one command character per line.
+
+pub const TICK_CHAR: char = '\'';
+
+/// A single command sent to the game after the seed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    Tick,
+    Key(char),
+}
+
+impl Input {
+    pub fn as_char(self) -> char {
+        match self {
+            Input::Tick => TICK_CHAR,
+            Input::Key(key) => key,
+        }
+    }
+
+    /// The exact line the game reads for this input.
+    pub fn encode(self) -> String {
+        format!("{}\n", self.as_char())
+    }
+}
+
+pub fn seed_line(seed: i32) -> String {
+    format!("{}\n", seed)
+}
@@ -0,0 +1,270 @@
+//! A Rust version of Railroad Runners, run in-process by the `reference`
+//! backend. It speaks the same protocol as the assignment: a seed line, then
+//! one command per line (`'` to tick, `w`/`a`/`s`/`d` to move, `q` to quit),
+//! redrawing the map and score after every command.
+//!
+//! The map scrolls down one row per tick. Each lane is fed from a queue of
+//! chunks picked with the assignment's random number generator, so the same
+//! seed always gives the same map. Crouching gets the player under a barrier,
+//! and jumping gets them onto a train, which they can then run along.
+
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+};
+
+const MAP_HEIGHT: usize = 15;
+const MAP_WIDTH: usize = 3;
+/// The row the player runs on, counting up from the bottom of the map.
+const PLAYER_ROW: usize = 1;
+/// Rows at the start of the game that are always empty.
+const SAFE_ROWS: usize = 6;
+/// How many ticks a jump or a crouch lasts.
+const ACTION_DURATION: u32 = 3;
+const TICK_SCORE: i32 = 1;
+const CASH_SCORE: i32 = 10;
+
+const RAND_MULTIPLIER: u32 = 1_103_515_245;
+const RAND_INCREMENT: u32 = 12_345;
+const RAND_MASK: u32 = 0x7FFF_FFFF;
+
+/// The pieces each lane is built from, bottom row first.
+const CHUNKS: [&str; 8] = [
+    "...",
+    ".....",
+    "..$$$..",
+    "..#...",
+    "..#..$..",
+    "..TTTTT..",
+    "..TTT$$$TTT..",
+    "...#...#...",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Block {
+    Empty,
+    Cash,
+    Barrier,
+    Train,
+}
+
+impl Block {
+    fn from_sprite(sprite: char) -> Block {
+        match sprite {
+            '$' => Block::Cash,
+            '#' => Block::Barrier,
+            'T' => Block::Train,
+            _ => Block::Empty,
+        }
+    }
+
+    fn sprite(self) -> char {
+        match self {
+            Block::Empty => '.',
+            Block::Cash => '$',
+            Block::Barrier => '#',
+            Block::Train => 'T',
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stance {
+    Running,
+    Jumping,
+    Crouching,
+}
+
+struct Player {
+    lane: usize,
+    stance: Stance,
+    /// Ticks left until a jump or crouch finishes.
+    action_ticks: u32,
+    on_train: bool,
+    score: i32,
+}
+
+impl Player {
+    fn sprite(&self) -> char {
+        match self.stance {
+            Stance::Running => '@',
+            Stance::Jumping => '^',
+            Stance::Crouching => '_',
+        }
+    }
+
+    fn start_action(&mut self, stance: Stance) {
+        if self.stance == Stance::Running {
+            self.stance = stance;
+            self.action_ticks = ACTION_DURATION;
+        }
+    }
+}
+
+/// The assignment's linear congruential generator.
+struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.state = self
+            .state
+            .wrapping_mul(RAND_MULTIPLIER)
+            .wrapping_add(RAND_INCREMENT)
+            & RAND_MASK;
+        self.state
+    }
+}
+
+struct Game {
+    /// The visible map, bottom row first.
+    map: VecDeque<[Block; MAP_WIDTH]>,
+    /// The blocks still to come in each lane.
+    upcoming: [VecDeque<Block>; MAP_WIDTH],
+    rng: Rng,
+    player: Player,
+}
+
+impl Game {
+    fn new(seed: i32) -> Self {
+        let mut game = Game {
+            map: VecDeque::new(),
+            upcoming: Default::default(),
+            // Negative seeds keep their bits, as they would in a MIPS register
+            rng: Rng { state: seed as u32 },
+            player: Player {
+                lane: MAP_WIDTH / 2,
+                stance: Stance::Running,
+                action_ticks: 0,
+                on_train: false,
+                score: 0,
+            },
+        };
+        game.map.extend([[Block::Empty; MAP_WIDTH]; SAFE_ROWS]);
+        while game.map.len() < MAP_HEIGHT {
+            let row = game.next_row();
+            game.map.push_back(row);
+        }
+        game
+    }
+
+    fn next_row(&mut self) -> [Block; MAP_WIDTH] {
+        let mut row = [Block::Empty; MAP_WIDTH];
+        for (lane, block) in row.iter_mut().enumerate() {
+            if self.upcoming[lane].is_empty() {
+                let chunk = CHUNKS[self.rng.next() as usize % CHUNKS.len()];
+                self.upcoming[lane].extend(chunk.chars().map(Block::from_sprite));
+            }
+            *block = self.upcoming[lane].pop_front().unwrap();
+        }
+        row
+    }
+
+    fn tick(&mut self) {
+        if self.player.action_ticks > 0 {
+            self.player.action_ticks -= 1;
+            if self.player.action_ticks == 0 {
+                self.player.stance = Stance::Running;
+            }
+        }
+        self.map.pop_front();
+        let row = self.next_row();
+        self.map.push_back(row);
+        self.player.score += TICK_SCORE;
+    }
+
+    /// Applies one command. Returns `false` if the game is over.
+    fn handle_command(&mut self, command: char) -> bool {
+        match command {
+            '\'' => self.tick(),
+            'a' => self.player.lane = self.player.lane.saturating_sub(1),
+            'd' => self.player.lane = (self.player.lane + 1).min(MAP_WIDTH - 1),
+            'w' => self.player.start_action(Stance::Jumping),
+            's' => self.player.start_action(Stance::Crouching),
+            'q' => return false,
+            _ => {}
+        }
+        self.handle_collision()
+    }
+
+    /// Deals with whatever the player is now standing on. Returns `false` if
+    /// they ran into something.
+    fn handle_collision(&mut self) -> bool {
+        let player = &mut self.player;
+        let block = &mut self.map[PLAYER_ROW][player.lane];
+        match *block {
+            Block::Barrier if player.stance != Stance::Crouching => return false,
+            Block::Train if player.stance != Stance::Jumping && !player.on_train => return false,
+            Block::Cash => {
+                player.score += CASH_SCORE;
+                *block = Block::Empty;
+            }
+            _ => {}
+        }
+        player.on_train = *block == Block::Train;
+        true
+    }
+
+    fn print(&self, output: &mut impl Write) -> io::Result<()> {
+        for (row, blocks) in self.map.iter().enumerate().rev() {
+            let mut line = String::from("|");
+            for (lane, block) in blocks.iter().enumerate() {
+                let sprite = match row == PLAYER_ROW && lane == self.player.lane {
+                    true => self.player.sprite(),
+                    false => block.sprite(),
+                };
+                line.push_str(&format!(" {} |", sprite));
+            }
+            writeln!(output, "{}", line)?;
+        }
+        writeln!(output, "Score: {}", self.player.score)?;
+        output.flush()
+    }
+}
+
+/// The next command, skipping the newlines between them. `None` once the input has ended.
+fn read_command(input: &mut impl Read) -> io::Result<Option<char>> {
+    let mut byte = [0];
+    loop {
+        if input.read(&mut byte)? == 0 {
+            return Ok(None);
+        }
+        if !byte[0].is_ascii_whitespace() {
+            return Ok(Some(byte[0] as char));
+        }
+    }
+}
+
+/// Plays a whole game, reading commands from `input` and drawing to `output`.
+pub fn run(input: impl Read, output: impl Write) -> io::Result<()> {
+    let mut input = BufReader::new(input);
+    let mut output = BufWriter::new(output);
+
+    writeln!(output, "Welcome to Railroad Runners!")?;
+    writeln!(output, "Use w, a, s and d to move, and ' to advance the game.")?;
+    writeln!(output, "Enter a non-negative seed: ")?;
+    output.flush()?;
+
+    let mut seed = String::new();
+    input.read_line(&mut seed)?;
+    let seed = seed
+        .trim()
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid seed"))?;
+
+    let mut game = Game::new(seed);
+    game.print(&mut output)?;
+    while let Some(command) = read_command(&mut input)? {
+        let alive = game.handle_command(command);
+        // Drawn even when the game is over, so the player can see what they hit
+        game.print(&mut output)?;
+        if !alive {
+            break;
+        }
+    }
+
+    writeln!(output, "Game over, thanks for playing!")?;
+    writeln!(output, "Final score: {}", game.player.score)?;
+    output.flush()
+}
@@ -0,0 +1,112 @@
+//! Draws whole frames to the terminal at once.
+//!
+//! The game is drawn on the alternate screen with the cursor hidden. Each draw
+//! homes the cursor and overwrites the previous frame in place, clearing any
+//! leftovers, in a single write, so the screen is never blank or half drawn.
+
+use crate::frame::Frame;
+use crate::hud::Hud;
+use crate::theme::Theme;
+use anyhow::Result;
+use console::Term;
+use std::io::Write;
+
+const ENTER_ALTERNATE_SCREEN: &str = "\x1b[?1049h";
+pub const LEAVE_ALTERNATE_SCREEN: &str = "\x1b[?1049l";
+const CURSOR_HOME: &str = "\x1b[H";
+const CLEAR_TO_END_OF_LINE: &str = "\x1b[K";
+const CLEAR_TO_END_OF_SCREEN: &str = "\x1b[J";
+
+pub struct Renderer {
+    term: Term,
+    theme: Theme,
+    frame: Vec<String>,
+    hud: Option<Hud>,
+    message: Option<String>,
+    active: bool,
+}
+
+impl Renderer {
+    /// Switches to the alternate screen. The terminal is restored when the
+    /// renderer is finished or dropped.
+    pub fn start(theme: Theme, hud: Option<Hud>) -> Result<Self> {
+        let mut term = Term::buffered_stdout();
+        term.write_all(ENTER_ALTERNATE_SCREEN.as_bytes())?;
+        term.hide_cursor()?;
+        term.flush()?;
+        Ok(Renderer {
+            term,
+            theme,
+            frame: Vec::new(),
+            hud,
+            message: None,
+            active: true,
+        })
+    }
+
+    pub fn set_frame(&mut self, frame: &Frame) {
+        // Only the output left after the last score line, like the game over
+        // message, goes under the last frame. Every complete frame replaces it,
+        // even if no board could be found in it.
+        let lines = frame.lines.iter().map(|line| self.theme.paint(line));
+        if frame.score.is_none() && frame.board.is_empty() {
+            self.frame.extend(lines);
+        } else {
+            self.frame = lines.collect();
+        }
+    }
+
+    /// The status bar shown under the board, if there is one.
+    pub fn hud(&mut self) -> Option<&mut Hud> {
+        self.hud.as_mut()
+    }
+
+    /// A message shown under everything else, e.g. while paused.
+    pub fn set_message(&mut self, message: Option<String>) {
+        self.message = message;
+    }
+
+    /// Redraws the screen with everything that has been set so far.
+    pub fn draw(&mut self) -> Result<()> {
+        let mut screen = String::from(CURSOR_HOME);
+        let status = self.hud.as_ref().map(Hud::lines).unwrap_or_default();
+        let lines = self.frame.iter().chain(&status).chain(&self.message);
+        for line in lines {
+            screen.push_str(line);
+            screen.push_str(CLEAR_TO_END_OF_LINE);
+            screen.push_str("\r\n");
+        }
+        screen.push_str(CLEAR_TO_END_OF_SCREEN);
+
+        self.term.write_all(screen.as_bytes())?;
+        self.term.flush()?;
+        Ok(())
+    }
+
+    /// Restores the terminal, then prints the last frame to the normal screen
+    /// so it stays visible after the game ends.
+    pub fn finish(mut self) -> Result<()> {
+        self.restore()?;
+        for line in &self.frame {
+            self.term.write_line(line)?;
+        }
+        self.term.flush()?;
+        Ok(())
+    }
+
+    fn restore(&mut self) -> Result<()> {
+        if self.active {
+            self.active = false;
+            self.term.show_cursor()?;
+            self.term.write_all(LEAVE_ALTERNATE_SCREEN.as_bytes())?;
+            self.term.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        let _ = self.restore();
+    }
+}
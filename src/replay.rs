@@ -0,0 +1,200 @@
+//! Replay files.
+//!
+//! A replay is a plain-text, line-based log of everything the wrapper wrote to
+//! the game's stdin, in the order it was written:
+//!
+//! ```text
+//! railroad-runners-replay 1
+//! program path/to/railroad-runners.s
+//! seed 1234
+//! tick 0 0
+//! key d 412345
+//! tick 1 1000120
+//! ```
+//!
+//! Ticks carry their sequence number, and both ticks and keys carry their
+//! offset from the start of the game in microseconds.
+
+use crate::protocol::Input;
+use anyhow::{bail, Context, Result};
+use std::{
+    fs::{self, File},
+    io::{LineWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const MAGIC: &str = "railroad-runners-replay";
+pub const REPLAY_VERSION: u32 = 1;
+
+/// Writes a replay file as the game is played.
+pub struct Recorder {
+    path: PathBuf,
+    writer: LineWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, program: &str, seed: i32) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create replay file {}", path.display()))?;
+        let mut recorder = Recorder {
+            path: path.to_path_buf(),
+            writer: LineWriter::new(file),
+        };
+        recorder.write_line(&format!("{} {}", MAGIC, REPLAY_VERSION))?;
+        recorder.write_line(&format!("program {}", program))?;
+        recorder.write_line(&format!("seed {}", seed))?;
+        Ok(recorder)
+    }
+
+    pub fn tick(&mut self, seq: u64, offset: Duration) -> Result<()> {
+        self.write_line(&format!("tick {} {}", seq, offset.as_micros()))
+    }
+
+    pub fn key(&mut self, key: char, offset: Duration) -> Result<()> {
+        self.write_line(&format!("key {} {}", key, offset.as_micros()))
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        // Every line is flushed straight away so a crash still leaves a usable replay
+        writeln!(self.writer, "{}", line)
+            .with_context(|| format!("Failed to write to replay file {}", self.path.display()))
+    }
+}
+
+/// An input as it appears in a replay, with its offset from the start of the game.
+#[derive(Clone, Copy, Debug)]
+pub struct ReplayEvent {
+    pub input: Input,
+    pub offset: Duration,
+}
+
+/// A replay file read back into memory.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    pub program: String,
+    pub seed: i32,
+    pub events: Vec<ReplayEvent>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read replay file {}", path.display()))?;
+        Self::parse(&contents).with_context(|| format!("Invalid replay file {}", path.display()))
+    }
+
+    fn parse(contents: &str) -> Result<Self> {
+        let mut lines = contents.lines().enumerate().map(|(i, line)| (i + 1, line));
+
+        match lines.next() {
+            Some((_, header)) => match header.strip_prefix(MAGIC) {
+                Some(version) if version.trim() == REPLAY_VERSION.to_string() => {}
+                Some(version) => bail!(
+                    "Unsupported replay version {} (expected {})",
+                    version.trim(),
+                    REPLAY_VERSION
+                ),
+                None => bail!("Not a replay file"),
+            },
+            None => bail!("Empty replay file"),
+        }
+
+        let mut program = None;
+        let mut seed = None;
+        let mut events = Vec::new();
+        let mut next_tick = 0;
+
+        for (line_number, line) in lines {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let parsed = match fields.as_slice() {
+                [] => Ok(()),
+                ["program", ..] => {
+                    program = Some(line["program".len()..].trim().to_string());
+                    Ok(())
+                }
+                ["seed", value] => value.parse().map(|value| seed = Some(value)).map_err(Into::into),
+                ["tick", seq, offset] => parse_tick(seq, offset, next_tick).map(|event| {
+                    next_tick += 1;
+                    events.push(event);
+                }),
+                ["key", key, offset] => parse_key(key, offset).map(|event| events.push(event)),
+                _ => Err(anyhow::anyhow!("Unrecognised entry")),
+            };
+            parsed.with_context(|| format!("Line {}: {}", line_number, line))?;
+        }
+
+        Ok(Replay {
+            program: program.context("Missing program line")?,
+            seed: seed.context("Missing seed line")?,
+            events,
+        })
+    }
+}
+
+fn parse_offset(offset: &str) -> Result<Duration> {
+    Ok(Duration::from_micros(offset.parse()?))
+}
+
+fn parse_tick(seq: &str, offset: &str, expected: u64) -> Result<ReplayEvent> {
+    let seq: u64 = seq.parse()?;
+    if seq != expected {
+        bail!("Expected tick {}, found tick {}", expected, seq);
+    }
+    Ok(ReplayEvent {
+        input: Input::Tick,
+        offset: parse_offset(offset)?,
+    })
+}
+
+fn parse_key(key: &str, offset: &str) -> Result<ReplayEvent> {
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(key), None) => Ok(ReplayEvent {
+            input: Input::Key(key),
+            offset: parse_offset(offset)?,
+        }),
+        _ => bail!("Keys must be a single character"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recordings_read_back() {
+        let path = std::env::temp_dir().join(format!("replay-test-{}.replay", std::process::id()));
+        let mut recorder = Recorder::create(&path, "path/to/game file.s", -42).unwrap();
+        recorder.tick(0, Duration::ZERO).unwrap();
+        recorder.key('d', Duration::from_micros(412_345)).unwrap();
+        recorder.tick(1, Duration::from_micros(1_000_120)).unwrap();
+        drop(recorder);
+
+        let replay = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        let replay = replay.unwrap();
+        assert_eq!(replay.program, "path/to/game file.s");
+        assert_eq!(replay.seed, -42);
+        let events: Vec<_> = replay.events.iter().map(|e| (e.input, e.offset.as_micros())).collect();
+        assert_eq!(
+            events,
+            [(Input::Tick, 0), (Input::Key('d'), 412_345), (Input::Tick, 1_000_120)]
+        );
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let error = Replay::parse("railroad-runners-replay 2\nprogram a.s\nseed 1\n").unwrap_err();
+        assert_eq!(error.to_string(), "Unsupported replay version 2 (expected 1)");
+        assert!(Replay::parse("something else\n").is_err());
+        assert!(Replay::parse("").is_err());
+    }
+
+    #[test]
+    fn ticks_must_be_in_order() {
+        let contents = "railroad-runners-replay 1\nprogram a.s\nseed 1\ntick 0 0\ntick 2 10\n";
+        let error = Replay::parse(contents).unwrap_err();
+        assert_eq!(format!("{:#}", error), "Line 5: tick 2 10: Expected tick 1, found tick 2");
+    }
+}
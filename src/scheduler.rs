@@ -0,0 +1,200 @@
+//! When to send each tick.
+//!
+//! Deadlines are absolute points in game time: each one is the previous
+//! deadline plus the speed curve's interval at that deadline, rather than the
+//! time the previous tick actually went out plus an interval. So a tick that
+//! goes out a little late doesn't push back all the ones after it, and the
+//! game keeps to the curve over a whole run. A tick that is more than a whole
+//! interval late starts the schedule again from when it was sent, instead of
+//! sending a burst of ticks to catch up.
+
+use crate::speed::SpeedCurve;
+use std::{fmt, time::Duration};
+
+/// How late a tick can be before it counts as late.
+pub const LATE_TOLERANCE: Duration = Duration::from_millis(5);
+
+/// The first tick is due straight away.
+#[derive(Default)]
+pub struct TickScheduler {
+    deadline: Duration,
+    /// The speed curve's interval when the last tick was sent.
+    last_interval: Option<Duration>,
+    last_sent: Option<Duration>,
+    stats: TickStats,
+}
+
+/// How closely the ticks kept to the schedule.
+#[derive(Clone, Debug, Default)]
+pub struct TickStats {
+    pub ticks: u64,
+    pub late_ticks: u64,
+    pub worst_lateness: Duration,
+    /// Ticks that missed their deadline waiting for the game to draw the last
+    /// one, in lockstep.
+    pub held_back: u64,
+    /// Ticks that gave up waiting for the game to draw the last one, in lockstep.
+    pub frames_missed: u64,
+    /// The scheduled times between ticks, added up.
+    intended_total: Duration,
+    /// The times the ticks actually went out apart, added up.
+    actual_total: Duration,
+    shortest_actual: Option<Duration>,
+    longest_actual: Duration,
+}
+
+impl TickScheduler {
+    /// The game time the next tick is due at.
+    pub fn deadline(&self) -> Duration {
+        self.deadline
+    }
+
+    /// Records the due tick going out at game time `sent`, and schedules the
+    /// next one. Returns the interval until the next tick.
+    pub fn tick_sent(&mut self, sent: Duration, speed_curve: &SpeedCurve) -> Duration {
+        let lateness = sent.saturating_sub(self.deadline);
+        let stats = &mut self.stats;
+        stats.ticks += 1;
+        if lateness > LATE_TOLERANCE {
+            stats.late_ticks += 1;
+            stats.worst_lateness = stats.worst_lateness.max(lateness);
+        }
+        if let (Some(last_interval), Some(last_sent)) = (self.last_interval, self.last_sent) {
+            let actual = sent - last_sent;
+            stats.intended_total += last_interval;
+            stats.actual_total += actual;
+            stats.shortest_actual = Some(stats.shortest_actual.map_or(actual, |s| s.min(actual)));
+            stats.longest_actual = stats.longest_actual.max(actual);
+        }
+        self.last_sent = Some(sent);
+
+        let interval = speed_curve.interval(self.deadline);
+        let interval = if lateness > interval {
+            let interval = speed_curve.interval(sent);
+            self.deadline = sent + interval;
+            interval
+        } else {
+            self.deadline += interval;
+            interval
+        };
+        self.last_interval = Some(interval);
+        self.deadline.saturating_sub(sent)
+    }
+
+    /// Records that the game finished drawing the last tick after the next
+    /// one was due, so the next one was held back.
+    pub fn held_back(&mut self) {
+        self.stats.held_back += 1;
+    }
+
+    /// Records that a tick went out without the game having finished drawing
+    /// the last one.
+    pub fn frame_missed(&mut self) {
+        self.stats.frames_missed += 1;
+    }
+
+    pub fn stats(&self) -> &TickStats {
+        &self.stats
+    }
+}
+
+impl fmt::Display for TickStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Ticks: {} sent, {} late by more than {}ms",
+            self.ticks,
+            self.late_ticks,
+            LATE_TOLERANCE.as_millis()
+        )?;
+        if self.late_ticks > 0 {
+            write!(f, " (worst {})", millis(self.worst_lateness))?;
+        }
+        let intervals = self.ticks.saturating_sub(1) as u32;
+        if let (Some(shortest), true) = (self.shortest_actual, intervals > 0) {
+            write!(
+                f,
+                "\nTick interval: {} intended on average, {} actual (shortest {}, longest {})",
+                millis(self.intended_total / intervals),
+                millis(self.actual_total / intervals),
+                millis(shortest),
+                millis(self.longest_actual)
+            )?;
+        }
+        if self.held_back > 0 {
+            write!(
+                f,
+                "\nThe game couldn't keep up with the speed curve: {} ticks waited for it to finish drawing",
+                self.held_back
+            )?;
+        }
+        if self.frames_missed > 0 {
+            write!(
+                f,
+                "\nThe game didn't finish a frame (a line starting with `Score:`) in time for {} ticks, so they went out without waiting",
+                self.frames_missed
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVE: SpeedCurve = SpeedCurve::Constant { interval: 0.1 };
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn ticks_on_time_keep_to_the_curve() {
+        let mut scheduler = TickScheduler::default();
+        assert_eq!(scheduler.deadline(), Duration::ZERO);
+        assert_eq!(scheduler.tick_sent(Duration::ZERO, &CURVE), ms(100));
+        assert_eq!(scheduler.tick_sent(ms(101), &CURVE), ms(99));
+        assert_eq!(scheduler.deadline(), ms(200));
+        assert_eq!(scheduler.stats().late_ticks, 0);
+    }
+
+    #[test]
+    fn a_late_tick_doesnt_push_back_the_rest() {
+        let mut scheduler = TickScheduler::default();
+        scheduler.tick_sent(Duration::ZERO, &CURVE);
+        assert_eq!(scheduler.tick_sent(ms(130), &CURVE), ms(70));
+        assert_eq!(scheduler.deadline(), ms(200));
+
+        let stats = scheduler.stats();
+        assert_eq!((stats.ticks, stats.late_ticks), (2, 1));
+        assert_eq!(stats.worst_lateness, ms(30));
+    }
+
+    #[test]
+    fn a_very_late_tick_restarts_the_schedule() {
+        let mut scheduler = TickScheduler::default();
+        scheduler.tick_sent(Duration::ZERO, &CURVE);
+        // Catching up would mean sending two ticks straight away
+        assert_eq!(scheduler.tick_sent(ms(350), &CURVE), ms(100));
+        assert_eq!(scheduler.deadline(), ms(450));
+        assert_eq!(scheduler.stats().worst_lateness, ms(250));
+    }
+
+    #[test]
+    fn stats_compare_intended_and_actual_intervals() {
+        let mut scheduler = TickScheduler::default();
+        for sent in [0, 100, 230, 300] {
+            scheduler.tick_sent(ms(sent), &CURVE);
+        }
+        assert_eq!(
+            scheduler.stats().to_string(),
+            "Ticks: 4 sent, 1 late by more than 5ms (worst 30.0ms)\n\
+             Tick interval: 100.0ms intended on average, 100.0ms actual (shortest 70.0ms, longest 130.0ms)"
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! The local high-score table, kept in the user's data directory.
+
+use crate::date::DateTime;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const SCORES_FILE: &str = "scores.toml";
+const RUNS_PER_SEED: usize = 3;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ScoreEntry {
+    pub score: i32,
+    pub seed: i32,
+    /// When the game finished, in UTC.
+    pub date: String,
+    pub duration_secs: f64,
+    pub player: String,
+    pub program: String,
+    /// Identifies the exact assignment file that was played.
+    pub program_hash: String,
+}
+
+impl ScoreEntry {
+    pub fn new(
+        score: i32,
+        seed: i32,
+        duration: Duration,
+        player: &str,
+        program: &str,
+        program_hash: String,
+    ) -> Self {
+        ScoreEntry {
+            score,
+            seed,
+            date: DateTime::now().to_string(),
+            duration_secs: duration.as_secs_f64(),
+            player: player.to_string(),
+            program: program.to_string(),
+            program_hash,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Leaderboard {
+    #[serde(default)]
+    scores: Vec<ScoreEntry>,
+}
+
+/// The directory the wrapper keeps its own files in.
+pub fn data_dir() -> Result<PathBuf> {
+    let dir = dirs::data_dir()
+        .context("Couldn't find a data directory to keep scores in")?
+        .join("railroad-runners");
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create data directory {}", dir.display()))?;
+    Ok(dir)
+}
+
+impl Leaderboard {
+    pub fn path() -> Result<PathBuf> {
+        Ok(data_dir()?.join(SCORES_FILE))
+    }
+
+    pub fn load() -> Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Leaderboard::default());
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read scores from {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Invalid scores file {}", path.display()))
+    }
+
+    fn save(&self) -> Result<()> {
+        let path = Self::path()?;
+        fs::write(&path, toml::to_string(self)?)
+            .with_context(|| format!("Failed to save scores to {}", path.display()))
+    }
+
+    /// Adds a run and saves the table. Returns the run's rank overall, from 1.
+    pub fn add(entry: ScoreEntry) -> Result<usize> {
+        let mut leaderboard = Self::load()?;
+        let rank = 1 + leaderboard
+            .scores
+            .iter()
+            .filter(|other| other.score > entry.score)
+            .count();
+        leaderboard.scores.push(entry);
+        leaderboard.save()?;
+        Ok(rank)
+    }
+
+    /// Prints the best runs overall and for each seed, optionally only for
+    /// one seed or one assignment file.
+    pub fn print(&self, seed: Option<i32>, program: Option<&Path>, limit: usize) -> Result<()> {
+        let program_hash = program.map(hash_file).transpose()?;
+        let mut scores: Vec<&ScoreEntry> = self
+            .scores
+            .iter()
+            .filter(|entry| seed.is_none_or(|seed| entry.seed == seed))
+            .filter(|entry| {
+                program_hash
+                    .as_ref()
+                    .is_none_or(|hash| &entry.program_hash == hash)
+            })
+            .collect();
+        if scores.is_empty() {
+            println!("No scores yet");
+            return Ok(());
+        }
+        scores.sort_by(|a, b| b.score.cmp(&a.score).then(a.date.cmp(&b.date)));
+
+        match seed {
+            Some(seed) => println!("Top runs for seed {}", seed),
+            None => println!("Top runs"),
+        }
+        print_table(scores.iter().take(limit).copied());
+
+        if seed.is_none() {
+            let mut by_seed: BTreeMap<i32, Vec<&ScoreEntry>> = BTreeMap::new();
+            for entry in &scores {
+                by_seed.entry(entry.seed).or_default().push(entry);
+            }
+            // Seeds with the best runs first, since `scores` is already sorted
+            let mut seeds: Vec<_> = by_seed.into_values().collect();
+            seeds.sort_by_key(|runs| std::cmp::Reverse(runs[0].score));
+            for runs in seeds.into_iter().take(limit) {
+                println!();
+                println!("Seed {}", runs[0].seed);
+                print_table(runs.into_iter().take(RUNS_PER_SEED));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn print_table<'a>(entries: impl Iterator<Item = &'a ScoreEntry>) {
+    println!(
+        "  {:>4}  {:>7}  {:>11}  {:<16}  {:<20}  {:>8}  file",
+        "#", "score", "seed", "player", "date", "time"
+    );
+    for (rank, entry) in entries.enumerate() {
+        println!(
+            "  {:>4}  {:>7}  {:>11}  {:<16}  {:<20}  {:>7.1}s  {} ({})",
+            rank + 1,
+            entry.score,
+            entry.seed,
+            entry.player,
+            entry.date,
+            entry.duration_secs,
+            entry.program,
+            &entry.program_hash[..8]
+        );
+    }
+}
+
+/// A 64-bit FNV-1a hash, which is stable across Rust versions unlike the
+/// standard library's hasher.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+pub fn hash_file(path: &Path) -> Result<String> {
+    let contents =
+        fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(format!("{:016x}", fnv1a(&contents)))
+}
+
+/// The name to put on the scoreboard when `--name` isn't given.
+pub fn default_player() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "anonymous".to_string())
+}
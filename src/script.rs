@@ -0,0 +1,92 @@
+//! Input scripts for headless runs.
+//!
+//! Each line is a tick number and a key, meaning "send this key once that
+//! many ticks have been sent":
+//!
+//! ```text
+//! # dodge right straight away, then jump after the fifth tick
+//! 0 d
+//! 5 jump
+//! ```
+//!
+//! Keys can be given as the character the game reads or as an action name.
+//! Blank lines and lines starting with `#` are ignored.
+
+use crate::keymap::Action;
+use crate::protocol::Input;
+use anyhow::{bail, Context, Result};
+use std::{fs, io::Read, path::Path};
+
+#[derive(Clone, Debug, Default)]
+pub struct Script {
+    /// `(tick, key)` pairs, sorted by tick. Keys on the same tick keep their file order.
+    keys: Vec<(u64, char)>,
+}
+
+impl Script {
+    /// Loads a script from a file, or from stdin if the path is `-`.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = if path == Path::new("-") {
+            let mut contents = String::new();
+            std::io::stdin()
+                .read_to_string(&mut contents)
+                .context("Failed to read script from stdin")?;
+            contents
+        } else {
+            fs::read_to_string(path)
+                .with_context(|| format!("Failed to read script {}", path.display()))?
+        };
+        Self::parse(&contents).with_context(|| format!("Invalid script {}", path.display()))
+    }
+
+    fn parse(contents: &str) -> Result<Self> {
+        let mut keys = Vec::new();
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = parse_entry(line).with_context(|| format!("Line {}: {}", line_number + 1, line))?;
+            keys.push(entry);
+        }
+        keys.sort_by_key(|(tick, _)| *tick);
+        Ok(Script { keys })
+    }
+
+    /// Turns the script into the exact sequence of inputs to send, ending with
+    /// a quit if the game is still going after `max_ticks` ticks.
+    pub fn inputs(&self, max_ticks: u64) -> Vec<Input> {
+        let mut inputs = Vec::new();
+        let mut keys = self.keys.iter().peekable();
+        for tick in 0..max_ticks {
+            while let Some((_, key)) = keys.next_if(|(at, _)| *at == tick) {
+                inputs.push(Input::Key(*key));
+            }
+            inputs.push(Input::Tick);
+        }
+        inputs.push(Input::Key(Action::Quit.command().unwrap()));
+        inputs
+    }
+}
+
+fn parse_entry(line: &str) -> Result<(u64, char)> {
+    let (tick, key) = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        [tick, key] => (*tick, *key),
+        _ => bail!("Expected 'tick key'"),
+    };
+    let tick = tick
+        .parse()
+        .with_context(|| format!("'{}' is not a tick number", tick))?;
+
+    let commands: Vec<char> = Action::ALL.iter().filter_map(|action| action.command()).collect();
+    let mut chars = key.chars();
+    let command = match (chars.next(), chars.next()) {
+        (Some(c), None) if commands.contains(&c) => c,
+        _ => key
+            .parse::<Action>()
+            .ok()
+            .and_then(Action::command)
+            .with_context(|| format!("'{}' is not a game key", key))?,
+    };
+    Ok((tick, command))
+}
@@ -0,0 +1,209 @@
+//! Cleaning up when the wrapper is interrupted or one of its threads panics.
+//!
+//! Normally everything is put back in order as the game ends. A signal or a
+//! panic skips all of that, which would leave the game running and the
+//! terminal however the renderer left it. So while a game is running, enough
+//! is kept here to kill it, restore the terminal and say how to reproduce the
+//! run before exiting.
+//!
+//! A game in the terminal gets the first signal as an event instead, so it
+//! can stop and clean up as usual. Only a second signal exits straight away.
+//! Headless and diff runs exit on the first signal, so a marking script's
+//! timeout doesn't leave a stuck program running.
+
+use crate::{game_loop::Event, render};
+use anyhow::{Context, Result};
+use signal_hook::{
+    consts::{SIGHUP, SIGINT, SIGTERM},
+    iterator::Signals,
+};
+use std::{
+    io::{self, Write},
+    mem::MaybeUninit,
+    os::fd::RawFd,
+    panic,
+    path::PathBuf,
+    process, ptr,
+    sync::{mpsc::Sender, Mutex, OnceLock},
+    thread,
+};
+
+const SHOW_CURSOR: &str = "\x1b[?25h";
+const SIGNAL_EXIT_BASE: i32 = 128;
+const PANIC_EXIT_CODE: i32 = 101;
+
+/// What's needed to clean up after the game that's running.
+struct RunningGame {
+    seed: i32,
+    record: Option<PathBuf>,
+    /// The game's processes: one per program being run, unless it's running in-process.
+    pids: Vec<u32>,
+    /// Whether the game is drawn in the terminal, which needs putting back afterwards.
+    in_terminal: bool,
+    /// Where to send the first signal, if the game can handle it itself.
+    events: Option<Sender<Event>>,
+}
+
+static RUNNING: Mutex<Option<RunningGame>> = Mutex::new(None);
+/// The terminal's settings from before the wrapper changed anything.
+static TERMINAL: OnceLock<Option<libc::termios>> = OnceLock::new();
+/// The terminal a [`TerminalInput`](crate::terminal::TerminalInput) has
+/// changed, which may not be stdin, and its settings from before.
+static TAKEN_TERMINAL: Mutex<Option<(RawFd, libc::termios)>> = Mutex::new(None);
+/// Held by whichever thread is exiting, so a panic caused by a signal doesn't
+/// clean up a second time.
+static EXITING: Mutex<()> = Mutex::new(());
+
+/// Sets up the panic hook and signal handling. Call this once, before
+/// anything touches the terminal.
+pub fn install() -> Result<()> {
+    TERMINAL.get_or_init(|| {
+        let mut termios = MaybeUninit::uninit();
+        match unsafe { libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) } {
+            0 => Some(unsafe { termios.assume_init() }),
+            _ => None,
+        }
+    });
+
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _exiting = EXITING.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let game = clean_up();
+        // The default hook prints the panic message, which needs the normal screen to be seen
+        default_hook(info);
+        report(game.as_ref());
+        process::exit(PANIC_EXIT_CODE);
+    }));
+
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP])
+        .context("Failed to set up signal handling")?;
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            let events = lock_running().as_mut().and_then(|game| game.events.take());
+            if events.is_some_and(|events| events.send(Event::Signal(signal)).is_ok()) {
+                continue;
+            }
+
+            let _exiting = EXITING.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let game = clean_up();
+            eprintln!();
+            eprintln!("Stopped by {}", signal_name(signal));
+            report(game.as_ref());
+            process::exit(signal_exit_code(signal).into());
+        }
+    });
+    Ok(())
+}
+
+/// Remembers the game that has just started in the terminal, until [`game_finished`].
+pub fn game_started(seed: i32, record: Option<PathBuf>, pid: Option<u32>) {
+    *lock_running() = Some(RunningGame {
+        seed,
+        record,
+        pids: pid.into_iter().collect(),
+        in_terminal: true,
+        events: None,
+    });
+}
+
+/// Remembers a game that is about to start without the terminal, until
+/// [`game_finished`]. Its processes are added with [`process_started`].
+pub fn scripted_game_started(seed: i32, record: Option<PathBuf>) {
+    *lock_running() = Some(RunningGame {
+        seed,
+        record,
+        pids: Vec::new(),
+        in_terminal: false,
+        events: None,
+    });
+}
+
+pub fn process_started(pid: Option<u32>) {
+    if let (Some(game), Some(pid)) = (lock_running().as_mut(), pid) {
+        game.pids.push(pid);
+    }
+}
+
+/// Forgets a process once it has been reaped, so its pid can't be killed after being reused.
+pub fn process_finished(pid: Option<u32>) {
+    if let Some(game) = lock_running().as_mut() {
+        game.pids.retain(|&running| Some(running) != pid);
+    }
+}
+
+/// Remembers the terminal `fd`'s settings from before a game changed them,
+/// until [`terminal_released`].
+pub fn terminal_taken(fd: RawFd, original: libc::termios) {
+    *lock_taken_terminal() = Some((fd, original));
+}
+
+pub fn terminal_released() {
+    lock_taken_terminal().take();
+}
+
+/// Sends the next signal to the running game's event loop.
+pub fn forward_signals(events: Sender<Event>) {
+    if let Some(game) = lock_running().as_mut() {
+        game.events = Some(events);
+    }
+}
+
+pub fn game_finished() {
+    lock_running().take();
+}
+
+pub fn signal_name(signal: i32) -> &'static str {
+    signal_hook::low_level::signal_name(signal).unwrap_or("a signal")
+}
+
+/// The exit code for being stopped by `signal`: 128 plus its number, as in the shell.
+pub fn signal_exit_code(signal: i32) -> u8 {
+    (SIGNAL_EXIT_BASE + signal) as u8
+}
+
+fn lock_running() -> std::sync::MutexGuard<'static, Option<RunningGame>> {
+    RUNNING.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn lock_taken_terminal() -> std::sync::MutexGuard<'static, Option<(RawFd, libc::termios)>> {
+    TAKEN_TERMINAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Kills and reaps the game, if one is running, and puts the terminal back.
+fn clean_up() -> Option<RunningGame> {
+    let game = lock_running().take();
+    for &pid in game.iter().flat_map(|game| &game.pids) {
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGKILL);
+            libc::waitpid(pid as libc::pid_t, ptr::null_mut(), 0);
+        }
+    }
+
+    if let Some((fd, termios)) = lock_taken_terminal().take() {
+        unsafe {
+            libc::tcflush(fd, libc::TCIFLUSH);
+            libc::tcsetattr(fd, libc::TCSANOW, &termios);
+        }
+    }
+    if let Some(Some(termios)) = TERMINAL.get() {
+        unsafe {
+            libc::tcflush(libc::STDIN_FILENO, libc::TCIFLUSH);
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, termios);
+        }
+    }
+    if game.as_ref().is_some_and(|game| game.in_terminal) {
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "{}{}", SHOW_CURSOR, render::LEAVE_ALTERNATE_SCREEN);
+        let _ = stdout.flush();
+    }
+    game
+}
+
+fn report(game: Option<&RunningGame>) {
+    if let Some(game) = game {
+        eprintln!("Seed was {}", game.seed);
+        if let Some(record) = &game.record {
+            eprintln!("Replay of the game so far saved to {}", record.display());
+        }
+    }
+}
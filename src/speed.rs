@@ -0,0 +1,397 @@
+//! Speed curves: how long to wait between ticks, given how long the game has
+//! been running.
+
+use anyhow::{bail, ensure, Context, Result};
+use std::{fmt, fs, str::FromStr, time::Duration};
+
+// The higher the number, the slower the game
+const GAME_SPEED_MULTIPLIER: f64 = 1.2;
+const MAX_INTERVAL: f64 = 1.0;
+/// The limits on any interval a curve is given. Much faster and the wrapper
+/// would spend all its time ticking; much slower and nothing would happen.
+const SHORTEST_INTERVAL: f64 = 0.001;
+const LONGEST_INTERVAL: f64 = 60.0;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpeedCurve {
+    /// The same interval for the whole game.
+    Constant { interval: f64 },
+    /// Moves from `start` to `end` over `duration` seconds, then stays at `end`.
+    Linear { start: f64, end: f64, duration: f64 },
+    /// Uses each interval for `step` seconds in turn, then stays on the last.
+    Stepped { step: f64, intervals: Vec<f64> },
+    /// The original curve: `multiplier / log10(elapsed)`, capped at one second.
+    Logarithmic { multiplier: f64 },
+    /// Interpolates linearly between `(elapsed, interval)` points.
+    Table { points: Vec<(f64, f64)> },
+}
+
+impl Default for SpeedCurve {
+    fn default() -> Self {
+        SpeedCurve::Logarithmic {
+            multiplier: GAME_SPEED_MULTIPLIER,
+        }
+    }
+}
+
+impl SpeedCurve {
+    /// The time to wait before the next tick, in seconds.
+    pub fn interval_secs(&self, elapsed: Duration) -> f64 {
+        let elapsed = elapsed.as_secs_f64();
+        match self {
+            SpeedCurve::Constant { interval } => *interval,
+            SpeedCurve::Linear {
+                start,
+                end,
+                duration,
+            } => {
+                let progress = if *duration > 0.0 {
+                    f64::min(1.0, elapsed / duration)
+                } else {
+                    1.0
+                };
+                start + (end - start) * progress
+            }
+            SpeedCurve::Stepped { step, intervals } => {
+                let level = (elapsed / step) as usize;
+                intervals[level.min(intervals.len() - 1)]
+            }
+            SpeedCurve::Logarithmic { multiplier } => {
+                const BASE: f64 = 10.0;
+                let num = f64::log(if elapsed < BASE { BASE } else { elapsed }, BASE);
+                f64::min(MAX_INTERVAL, multiplier / num)
+            }
+            SpeedCurve::Table { points } => interpolate(points, elapsed),
+        }
+    }
+
+    pub fn interval(&self, elapsed: Duration) -> Duration {
+        // The logarithmic curve keeps getting faster, so it can end up below the limit
+        Duration::from_secs_f64(self.interval_secs(elapsed).max(SHORTEST_INTERVAL))
+    }
+
+    /// The curve's internal state at `elapsed`, for debugging.
+    pub fn debug_values(&self, elapsed: Duration) -> String {
+        let elapsed = elapsed.as_secs_f64();
+        match self {
+            SpeedCurve::Constant { .. } => "constant".to_string(),
+            SpeedCurve::Linear { duration, .. } => format!(
+                "progress {:.0}%",
+                100.0 * f64::min(1.0, elapsed / duration.max(f64::EPSILON))
+            ),
+            SpeedCurve::Stepped { step, intervals } => format!(
+                "level {}/{}",
+                ((elapsed / step) as usize).min(intervals.len() - 1) + 1,
+                intervals.len()
+            ),
+            SpeedCurve::Logarithmic { multiplier } => {
+                let num = f64::log10(elapsed.max(10.0));
+                format!("num {:.4}, uncapped {:.4}", num, multiplier / num)
+            }
+            SpeedCurve::Table { points } => {
+                let after = points.partition_point(|(time, _)| *time <= elapsed);
+                match (after.checked_sub(1).map(|i| points[i].0), points.get(after)) {
+                    (Some(from), Some((to, _))) => format!("between {}s and {}s", from, to),
+                    (Some(from), None) => format!("past the last point at {}s", from),
+                    (None, _) => format!("before the first point at {}s", points[0].0),
+                }
+            }
+        }
+    }
+
+    fn validate(self) -> Result<Self> {
+        let intervals: Vec<f64> = match &self {
+            SpeedCurve::Constant { interval } => vec![*interval],
+            SpeedCurve::Linear {
+                start,
+                end,
+                duration,
+            } => {
+                ensure!(*duration >= 0.0, "The duration can't be negative");
+                vec![*start, *end]
+            }
+            SpeedCurve::Stepped { step, intervals } => {
+                ensure!(*step > 0.0, "The step length must be positive");
+                ensure!(!intervals.is_empty(), "At least one interval is needed");
+                intervals.clone()
+            }
+            SpeedCurve::Logarithmic { multiplier } => vec![*multiplier],
+            SpeedCurve::Table { points } => {
+                ensure!(!points.is_empty(), "At least one point is needed");
+                ensure!(
+                    points.windows(2).all(|pair| pair[0].0 < pair[1].0),
+                    "Times must be strictly increasing"
+                );
+                points.iter().map(|(_, interval)| *interval).collect()
+            }
+        };
+        ensure!(
+            intervals
+                .iter()
+                .all(|interval| (SHORTEST_INTERVAL..=LONGEST_INTERVAL).contains(interval)),
+            "Intervals must be between {} and {} seconds",
+            SHORTEST_INTERVAL,
+            LONGEST_INTERVAL
+        );
+        Ok(self)
+    }
+}
+
+fn interpolate(points: &[(f64, f64)], elapsed: f64) -> f64 {
+    let after = points.partition_point(|(time, _)| *time <= elapsed);
+    if after == 0 {
+        return points[0].1;
+    }
+    if after == points.len() {
+        return points[after - 1].1;
+    }
+    let (t0, i0) = points[after - 1];
+    let (t1, i1) = points[after];
+    i0 + (i1 - i0) * (elapsed - t0) / (t1 - t0)
+}
+
+fn parse_number(value: &str) -> Result<f64> {
+    value
+        .trim()
+        .parse()
+        .with_context(|| format!("'{}' is not a number", value.trim()))
+}
+
+fn parse_list(values: &str) -> Result<Vec<f64>> {
+    values.split(',').map(parse_number).collect()
+}
+
+fn parse_points(points: &str) -> Result<Vec<(f64, f64)>> {
+    points
+        .split(',')
+        .map(|point| {
+            let (time, interval) = point
+                .split_once('=')
+                .with_context(|| format!("Expected time=interval, found '{}'", point))?;
+            Ok((parse_number(time)?, parse_number(interval)?))
+        })
+        .collect()
+}
+
+/// Reads a table file with one `time interval` pair per line. Blank lines and
+/// lines starting with `#` are ignored.
+fn load_points(path: &str) -> Result<Vec<(f64, f64)>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read speed table {}", path))?;
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [time, interval] => Ok((parse_number(time)?, parse_number(interval)?)),
+            _ => bail!("Expected 'time interval', found '{}'", line),
+        })
+        .collect()
+}
+
+impl FromStr for SpeedCurve {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, params) = match s.split_once(':') {
+            Some((name, params)) => (name, Some(params)),
+            None => (s, None),
+        };
+
+        let curve = match (name, params) {
+            ("constant", Some(interval)) => SpeedCurve::Constant {
+                interval: parse_number(interval)?,
+            },
+            ("linear", Some(params)) => match parse_list(params)?.as_slice() {
+                [start, end, duration] => SpeedCurve::Linear {
+                    start: *start,
+                    end: *end,
+                    duration: *duration,
+                },
+                _ => bail!("Expected linear:start,end,duration"),
+            },
+            ("stepped", Some(params)) => {
+                let (step, intervals) = params
+                    .split_once(':')
+                    .context("Expected stepped:step:interval,interval,...")?;
+                SpeedCurve::Stepped {
+                    step: parse_number(step)?,
+                    intervals: parse_list(intervals)?,
+                }
+            }
+            ("log", None) => SpeedCurve::default(),
+            ("log", Some(multiplier)) => SpeedCurve::Logarithmic {
+                multiplier: parse_number(multiplier)?,
+            },
+            ("table", Some(params)) => SpeedCurve::Table {
+                points: match params.strip_prefix('@') {
+                    Some(path) => load_points(path)?,
+                    None => parse_points(params)?,
+                },
+            },
+            ("constant" | "linear" | "stepped" | "table", None) => {
+                bail!("The {} curve needs parameters, e.g. {}", name, example(name))
+            }
+            _ => bail!(
+                "Unknown speed curve '{}'. Expected constant, linear, stepped, log or table",
+                name
+            ),
+        };
+        curve.validate()
+    }
+}
+
+fn example(name: &str) -> &'static str {
+    match name {
+        "constant" => "constant:0.5",
+        "linear" => "linear:1.0,0.3,120",
+        "stepped" => "stepped:30:1.0,0.8,0.6",
+        _ => "table:0=1.0,60=0.5",
+    }
+}
+
+impl fmt::Display for SpeedCurve {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |values: Vec<String>| values.join(",");
+        match self {
+            SpeedCurve::Constant { interval } => write!(f, "constant:{}", interval),
+            SpeedCurve::Linear {
+                start,
+                end,
+                duration,
+            } => write!(f, "linear:{},{},{}", start, end, duration),
+            SpeedCurve::Stepped { step, intervals } => write!(
+                f,
+                "stepped:{}:{}",
+                step,
+                join(intervals.iter().map(f64::to_string).collect())
+            ),
+            SpeedCurve::Logarithmic { multiplier } => write!(f, "log:{}", multiplier),
+            SpeedCurve::Table { points } => write!(
+                f,
+                "table:{}",
+                join(points.iter().map(|(t, i)| format!("{}={}", t, i)).collect())
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve(s: &str) -> SpeedCurve {
+        s.parse().unwrap()
+    }
+
+    fn error(s: &str) -> String {
+        s.parse::<SpeedCurve>().unwrap_err().to_string()
+    }
+
+    fn at(curve: &SpeedCurve, elapsed: f64) -> f64 {
+        curve.interval_secs(Duration::from_secs_f64(elapsed))
+    }
+
+    #[test]
+    fn parses_every_syntax() {
+        assert_eq!(curve("constant:0.5"), SpeedCurve::Constant { interval: 0.5 });
+        assert_eq!(
+            curve("linear:1.0,0.3,120"),
+            SpeedCurve::Linear {
+                start: 1.0,
+                end: 0.3,
+                duration: 120.0
+            }
+        );
+        assert_eq!(
+            curve("stepped:30:1.0,0.8,0.6"),
+            SpeedCurve::Stepped {
+                step: 30.0,
+                intervals: vec![1.0, 0.8, 0.6]
+            }
+        );
+        assert_eq!(curve("log"), SpeedCurve::default());
+        assert_eq!(curve("log:1.5"), SpeedCurve::Logarithmic { multiplier: 1.5 });
+        assert_eq!(
+            curve("table:0=1.0, 60=0.5"),
+            SpeedCurve::Table {
+                points: vec![(0.0, 1.0), (60.0, 0.5)]
+            }
+        );
+    }
+
+    #[test]
+    fn displays_as_it_parses() {
+        let curves = [
+            "constant:0.5",
+            "linear:1,0.3,120",
+            "stepped:30:1,0.8",
+            "log:1.2",
+            "table:0=1,60=0.5",
+        ];
+        for s in curves {
+            assert_eq!(curve(s).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_curves() {
+        assert_eq!(error("constant"), "The constant curve needs parameters, e.g. constant:0.5");
+        assert!(error("fast").starts_with("Unknown speed curve 'fast'"));
+        assert_eq!(error("constant:quick"), "'quick' is not a number");
+        assert_eq!(error("linear:1,0.5"), "Expected linear:start,end,duration");
+        assert_eq!(error("table:0=1,0=0.5"), "Times must be strictly increasing");
+        assert_eq!(error("stepped:0:1"), "The step length must be positive");
+        assert_eq!(error("linear:1,0.5,-1"), "The duration can't be negative");
+    }
+
+    #[test]
+    fn intervals_must_be_within_limits() {
+        for s in ["constant:0.001", "constant:60"] {
+            assert!(s.parse::<SpeedCurve>().is_ok(), "{}", s);
+        }
+        let out_of_range = [
+            "constant:0.0009",
+            "constant:61",
+            "constant:1e300",
+            "constant:NaN",
+            "log:0",
+            "table:0=1,10=0",
+        ];
+        for s in out_of_range {
+            assert_eq!(error(s), "Intervals must be between 0.001 and 60 seconds", "{}", s);
+        }
+    }
+
+    #[test]
+    fn intervals_follow_the_curve() {
+        let linear = curve("linear:1,0.5,10");
+        assert_eq!(at(&linear, 0.0), 1.0);
+        assert_eq!(at(&linear, 5.0), 0.75);
+        assert_eq!(at(&linear, 100.0), 0.5);
+
+        let stepped = curve("stepped:10:1,0.8,0.6");
+        assert_eq!(at(&stepped, 9.9), 1.0);
+        assert_eq!(at(&stepped, 10.0), 0.8);
+        assert_eq!(at(&stepped, 1000.0), 0.6);
+
+        let log = SpeedCurve::default();
+        assert_eq!(at(&log, 0.0), MAX_INTERVAL);
+        assert!((at(&log, 100.0) - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tables_interpolate_between_points() {
+        let table = curve("table:10=1,20=0.5,40=0.1");
+        assert_eq!(at(&table, 0.0), 1.0);
+        assert_eq!(at(&table, 15.0), 0.75);
+        assert!((at(&table, 30.0) - 0.3).abs() < 1e-9);
+        assert_eq!(at(&table, 100.0), 0.1);
+    }
+
+    #[test]
+    fn intervals_never_drop_below_the_limit() {
+        let log = curve("log:0.001");
+        assert_eq!(log.interval(Duration::from_secs(1000)), Duration::from_millis(1));
+    }
+}
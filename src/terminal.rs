@@ -0,0 +1,91 @@
+//! Holding on to the terminal's input for the whole game.
+//!
+//! Between key presses the terminal would normally be back in line mode,
+//! echoing whatever is typed and holding it until Enter, so keys pressed as
+//! the game ends would turn up in the shell afterwards. Instead, echo and line
+//! buffering stay off until the game is over, and anything left unread is
+//! thrown away before the terminal is restored.
+//!
+//! Keys are still read with `console`, which uses the same terminal: stdin if
+//! it is one, otherwise `/dev/tty`.
+
+use crate::shutdown;
+use anyhow::{Context, Result};
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    mem::MaybeUninit,
+    os::fd::{AsRawFd, RawFd},
+    time::Duration,
+};
+
+pub struct TerminalInput {
+    fd: RawFd,
+    original: libc::termios,
+    /// Kept open for `fd` when stdin isn't a terminal.
+    _tty: Option<File>,
+}
+
+impl TerminalInput {
+    pub fn start() -> Result<Self> {
+        let tty = match unsafe { libc::isatty(libc::STDIN_FILENO) } {
+            1 => None,
+            _ => Some(
+                OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .open("/dev/tty")
+                    .context("The game needs a terminal to read keys from")?,
+            ),
+        };
+        let fd = tty.as_ref().map_or(libc::STDIN_FILENO, |tty| tty.as_raw_fd());
+
+        let mut termios = MaybeUninit::uninit();
+        if unsafe { libc::tcgetattr(fd, termios.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error()).context("Failed to read the terminal's settings");
+        }
+        let original = unsafe { termios.assume_init() };
+
+        let mut settings = original;
+        settings.c_lflag &= !(libc::ICANON | libc::ECHO);
+        settings.c_cc[libc::VMIN] = 1;
+        settings.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &settings) } != 0 {
+            return Err(io::Error::last_os_error()).context("Failed to set up the terminal");
+        }
+        shutdown::terminal_taken(fd, original);
+
+        Ok(TerminalInput {
+            fd,
+            original,
+            _tty: tty,
+        })
+    }
+
+    /// Waits up to `timeout` for a key press. Returns whether there's one to read.
+    pub fn wait_for_key(&self, timeout: Duration) -> io::Result<bool> {
+        let mut poll_fd = libc::pollfd {
+            fd: self.fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+        match unsafe { libc::poll(&mut poll_fd, 1, timeout) } {
+            -1 => match io::Error::last_os_error() {
+                error if error.kind() == io::ErrorKind::Interrupted => Ok(false),
+                error => Err(error),
+            },
+            ready => Ok(ready > 0),
+        }
+    }
+}
+
+impl Drop for TerminalInput {
+    fn drop(&mut self) {
+        shutdown::terminal_released();
+        unsafe {
+            libc::tcflush(self.fd, libc::TCIFLUSH);
+            libc::tcsetattr(self.fd, libc::TCSANOW, &self.original);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! Colour themes for the board.
+//!
+//! A theme gives each kind of cell a [`console::Style`], and can swap
+//! individual characters for something easier to read. Theme files are TOML:
+//!
+//! ```toml
+//! base = "classic"
+//!
+//! [styles]
+//! train = "bold.red"
+//! coin = "214"
+//!
+//! [replacements]
+//! "#" = "▒"
+//! ```
+//!
+//! Styles use console's dotted syntax: colour names, `on_` backgrounds,
+//! attributes like `bold`, and 256-colour numbers. Replacements should be a
+//! single column wide or the board will no longer line up.
+
+use crate::frame::{self, Cell};
+use anyhow::{bail, Context, Result};
+use console::Style;
+use serde::Deserialize;
+use std::{collections::HashMap, fs, path::Path};
+
+const CELL_NAMES: [(Cell, &str); 7] = [
+    (Cell::Player, "player"),
+    (Cell::Train, "train"),
+    (Cell::Barrier, "barrier"),
+    (Cell::Coin, "coin"),
+    (Cell::PowerUp, "power_up"),
+    (Cell::Rail, "rail"),
+    (Cell::Wall, "wall"),
+];
+
+type Styles = &'static [(Cell, &'static str)];
+type Replacements = &'static [(char, &'static str)];
+
+pub const BUILT_IN: [&str; 5] = ["plain", "classic", "unicode", "colour-blind", "high-contrast"];
+
+#[derive(Clone, Debug, Default)]
+pub struct Theme {
+    styles: HashMap<Cell, Style>,
+    replacements: HashMap<char, String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    base: Option<String>,
+    #[serde(default)]
+    styles: HashMap<String, String>,
+    #[serde(default)]
+    replacements: HashMap<char, String>,
+}
+
+impl Theme {
+    /// Looks `name` up among the built-in themes, or loads it as a theme file.
+    pub fn load(name: &str) -> Result<Self> {
+        if let Some(theme) = Self::built_in(name) {
+            return Ok(theme);
+        }
+
+        let path = Path::new(name);
+        if !path.exists() {
+            bail!(
+                "No theme file or built-in theme called '{}'. The built-in themes are {}",
+                name,
+                BUILT_IN.join(", ")
+            );
+        }
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read theme file {}", path.display()))?;
+        let file: ThemeFile = toml::from_str(&contents)
+            .with_context(|| format!("Invalid theme file {}", path.display()))?;
+        Self::from_file(file).with_context(|| format!("Invalid theme file {}", path.display()))
+    }
+
+    fn from_file(file: ThemeFile) -> Result<Self> {
+        let mut theme = match &file.base {
+            Some(base) => Self::built_in(base)
+                .with_context(|| format!("Unknown base theme '{}'", base))?,
+            None => Theme::default(),
+        };
+        for (name, style) in &file.styles {
+            let cell = CELL_NAMES
+                .iter()
+                .find(|(_, cell_name)| cell_name == name)
+                .map(|(cell, _)| *cell)
+                .with_context(|| format!("Unknown cell '{}'", name))?;
+            theme.styles.insert(cell, parse_style(style)?);
+        }
+        theme.replacements.extend(file.replacements);
+        Ok(theme)
+    }
+
+    /// The built-in theme called `name`, including aliases like `color-blind`.
+    pub fn built_in(name: &str) -> Option<Self> {
+        let (styles, replacements): (Styles, Replacements) = match name {
+            "plain" => (&[], &[]),
+            "classic" => (CLASSIC, &[]),
+            "unicode" => (CLASSIC, UNICODE),
+            "colour-blind" | "color-blind" => (COLOUR_BLIND, &[]),
+            "high-contrast" => (HIGH_CONTRAST, &[]),
+            _ => return None,
+        };
+        Some(Theme {
+            styles: styles
+                .iter()
+                .map(|(cell, style)| (*cell, Style::from_dotted_str(style)))
+                .collect(),
+            replacements: replacements
+                .iter()
+                .map(|(c, replacement)| (*c, replacement.to_string()))
+                .collect(),
+        })
+    }
+
+    /// Styles a line of output. Only board rows are touched.
+    pub fn paint(&self, line: &str) -> String {
+        if !frame::is_board_row(line) || (self.styles.is_empty() && self.replacements.is_empty()) {
+            return line.to_string();
+        }
+
+        let mut painted = String::new();
+        for c in line.chars() {
+            let glyph = match self.replacements.get(&c) {
+                Some(replacement) => replacement.clone(),
+                None => c.to_string(),
+            };
+            match self.styles.get(&Cell::classify(c)) {
+                Some(style) => painted.push_str(&style.apply_to(glyph).to_string()),
+                None => painted.push_str(&glyph),
+            }
+        }
+        painted
+    }
+}
+
+const CLASSIC: Styles = &[
+    (Cell::Player, "bold.cyan"),
+    (Cell::Train, "red"),
+    (Cell::Barrier, "magenta"),
+    (Cell::Coin, "yellow"),
+    (Cell::PowerUp, "bold.green"),
+    (Cell::Rail, "dim"),
+    (Cell::Wall, "dim"),
+];
+
+const UNICODE: Replacements = &[
+    ('|', "│"),
+    ('.', "·"),
+    ('#', "▒"),
+    ('T', "█"),
+    ('=', "█"),
+    ('$', "●"),
+    ('*', "★"),
+];
+
+// Based on the Okabe-Ito palette, which avoids red/green distinctions
+const COLOUR_BLIND: Styles = &[
+    (Cell::Player, "bold.15"),
+    (Cell::Train, "208"),
+    (Cell::Barrier, "32"),
+    (Cell::Coin, "226"),
+    (Cell::PowerUp, "bold.39"),
+    (Cell::Rail, "dim"),
+    (Cell::Wall, "dim"),
+];
+
+const HIGH_CONTRAST: Styles = &[
+    (Cell::Player, "bold.black.on_white"),
+    (Cell::Train, "bold.white.on_red"),
+    (Cell::Barrier, "bold.black.on_yellow"),
+    (Cell::Coin, "bold.yellow.bright"),
+    (Cell::PowerUp, "bold.black.on_green"),
+    (Cell::Rail, "white"),
+    (Cell::Wall, "bold.white"),
+];
+
+/// Checks a dotted style string, since console silently ignores anything it doesn't know.
+fn parse_style(style: &str) -> Result<Style> {
+    const COLOURS: &[&str] = &[
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white", "bright",
+    ];
+    const ATTRIBUTES: &[&str] = &[
+        "bold", "dim", "underlined", "blink", "blink_fast", "reverse", "hidden", "strikethrough",
+    ];
+    for part in style.split('.') {
+        let known = match part.strip_prefix("on_") {
+            Some(colour) => COLOURS.contains(&colour) || colour.parse::<u8>().is_ok(),
+            None => {
+                COLOURS.contains(&part) || ATTRIBUTES.contains(&part) || part.parse::<u8>().is_ok()
+            }
+        };
+        if !known {
+            bail!("Unknown style '{}' in '{}'", part, style);
+        }
+    }
+    Ok(Style::from_dotted_str(style))
+}